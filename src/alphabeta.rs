@@ -1,5 +1,15 @@
 use crate::types::*;
-use crate::othello::*;
+use crate::position::*;
+use crate::transposition::*;
+use crate::endgame::*;
+use crate::ordering::*;
+use crate::eval::*;
+use crate::book::*;
+
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 //#################################################################################################
 //
@@ -8,170 +18,908 @@ use crate::othello::*;
 //#################################################################################################
 
 /*
- * The type describing an AI using the minimax algorithm with alpha-beta pruning.
+ * The maximum depth an iterative deepening search can reach, enough to go through every ply and
+ * pass of a game.
+ */
+const MAX_DEPTH: u8 = 64;
+
+/*
+ * The depth stored in the transposition table for positions whose whole subtree was searched,
+ * which are valid whatever depth they are probed at.
+ */
+const DEPTH_TO_END: u8 = u8::MAX;
+
+/*
+ * The default size of the transposition table, in megabytes.
+ */
+const DEFAULT_HASH_SIZE: usize = 16;
+
+/*
+ * The default number of empty squares from which the AI solves the game perfectly.
+ */
+const DEFAULT_ENDGAME_EMPTIES: u8 = 16;
+
+/*
+ * The default number of empty squares from which the AI looks for a winning or drawing move.
+ */
+const DEFAULT_WLD_EMPTIES: u8 = 20;
+
+/*
+ * The value of a won game in the midgame search, to which the final disc difference is added so
+ * that bigger wins are preferred.
+ */
+const WIN_VALUE: i32 = 1_000_000;
+
+/*
+ * A value greater than any value a position can have, whose opposite is still an i32.
+ */
+const INFINITY: i32 = i32::MAX;
+
+/*
+ * The maximum number of plies from the root, passes included, a search can go through.
+ */
+const MAX_PLY: usize = MAX_DEPTH as usize + 2;
+
+/*
+ * The half-width of the first aspiration window around the value of the previous iteration.
+ */
+const ASPIRATION_WINDOW: i32 = 50;
+
+/*
+ * Returns the default number of threads of the AI, one per available core.
+ */
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/*
+ * A time budget for the AI, either given anew for each move or shared by all the moves of a game.
+ */
+#[derive(Clone, Copy)]
+pub enum TimeControl {
+    PerMove(Duration),
+    PerGame(Duration),
+}
+
+/*
+ * The type describing an AI using the negamax algorithm with alpha-beta pruning. When given a
+ * time control, the AI deepens its search iteratively until its budget for the move runs out.
+ * The search runs on several threads sharing a transposition table, which is kept from one
+ * search to the next.
  */
 pub struct AlphaBetaPlayer {
     max_depth: u8,
+    time_control: Option<TimeControl>,
+    remaining: Cell<Duration>,
+    table: TranspositionTable,
+    endgame_empties: u8,
+    wld_empties: u8,
+    threads: usize,
+    ordering: MoveOrdering,
+    evaluator: Evaluator,
+    book: Option<Arc<Book>>,
+    book_randomness: f32,
+    rng: RefCell<Rng>,
 }
 
 impl AlphaBetaPlayer {
     /*
-     * Creates a new AlphaBetaPlayer AI.
+     * Creates a new AlphaBetaPlayer AI searching at a fixed depth.
      */
     pub fn new(max_depth: u8) -> AlphaBetaPlayer {
-        AlphaBetaPlayer { max_depth, }
+        AlphaBetaPlayer {
+            max_depth,
+            time_control: None,
+            remaining: Cell::new(Duration::from_secs(0)),
+            table: TranspositionTable::new(DEFAULT_HASH_SIZE),
+            endgame_empties: DEFAULT_ENDGAME_EMPTIES,
+            wld_empties: DEFAULT_WLD_EMPTIES,
+            threads: default_threads(),
+            ordering: MoveOrdering::default(),
+            evaluator: Evaluator::Masks,
+            book: None,
+            book_randomness: 0.0,
+            rng: RefCell::new(Rng::from_time()),
+        }
+    }
+
+    /*
+     * Creates a new AlphaBetaPlayer AI searching as deep as the given time control allows. With
+     * a per-game budget, the clock starts running at the first move played by the AI.
+     */
+    pub fn with_time(time_control: TimeControl) -> AlphaBetaPlayer {
+        let remaining = match time_control {
+            TimeControl::PerMove(_) => Duration::from_secs(0),
+            TimeControl::PerGame(total) => total,
+        };
+
+        AlphaBetaPlayer {
+            max_depth: MAX_DEPTH,
+            time_control: Some(time_control),
+            remaining: Cell::new(remaining),
+            table: TranspositionTable::new(DEFAULT_HASH_SIZE),
+            endgame_empties: DEFAULT_ENDGAME_EMPTIES,
+            wld_empties: DEFAULT_WLD_EMPTIES,
+            threads: default_threads(),
+            ordering: MoveOrdering::default(),
+            evaluator: Evaluator::Masks,
+            book: None,
+            book_randomness: 0.0,
+            rng: RefCell::new(Rng::from_time()),
+        }
+    }
+
+    /*
+     * Replaces the transposition table of the AI with one of the given size in megabytes.
+     */
+    pub fn with_hash_size(mut self, megabytes: usize) -> AlphaBetaPlayer {
+        self.table = TranspositionTable::new(megabytes);
+        self
+    }
+
+    /*
+     * Sets the number of empty squares from which the AI stops using its evaluation function and
+     * solves the game perfectly, playing the move with the best final disc difference.
+     */
+    pub fn with_endgame(mut self, empties: u8) -> AlphaBetaPlayer {
+        self.endgame_empties = empties;
+        self
+    }
+
+    /*
+     * Sets the number of empty squares from which the AI solves whether the game is won, drawn
+     * or lost, playing a winning or drawing move if there is one. Lost positions are left to the
+     * regular search, that may still find a way out against an imperfect opponent.
+     */
+    pub fn with_wld(mut self, empties: u8) -> AlphaBetaPlayer {
+        self.wld_empties = empties;
+        self
+    }
+
+    /*
+     * Sets the number of threads searching in parallel.
+     */
+    pub fn with_threads(mut self, threads: usize) -> AlphaBetaPlayer {
+        self.threads = std::cmp::max(1, threads);
+        self
+    }
+
+    /*
+     * Sets the policy used to order the moves during the search.
+     */
+    pub fn with_ordering(mut self, ordering: MoveOrdering) -> AlphaBetaPlayer {
+        self.ordering = ordering;
+        self
+    }
+
+    /*
+     * Sets the function evaluating the positions at the horizon of the search.
+     */
+    pub fn with_evaluator(mut self, evaluator: Evaluator) -> AlphaBetaPlayer {
+        self.evaluator = evaluator;
+        self
+    }
+
+    /*
+     * Sets the opening book the AI plays from while the position is in it.
+     */
+    pub fn with_book(mut self, book: Arc<Book>) -> AlphaBetaPlayer {
+        self.book = Some(book);
+        self
+    }
+
+    /*
+     * Sets how far below the best book move, in discs, the AI may pick a book move at random, so
+     * that it varies its openings.
+     */
+    pub fn with_book_randomness(mut self, randomness: f32) -> AlphaBetaPlayer {
+        self.book_randomness = randomness.max(0.0);
+        self
+    }
+
+    /*
+     * Returns the time the AI may spend on the next move. A per-game budget is split evenly
+     * between the moves the AI will likely still have to play.
+     */
+    fn budget(&self, pos: Position) -> Option<Duration> {
+        match self.time_control? {
+            TimeControl::PerMove(per_move) => Some(per_move),
+            TimeControl::PerGame(_) => {
+                let moves_left = pos.empties() as u32 / 2 + 1;
+                Some(self.remaining.get() / moves_left)
+            },
+        }
+    }
+
+    /*
+     * Takes the time spent on a move off the per-game budget, if there is one.
+     */
+    fn spend(&self, elapsed: Duration) {
+        if let Some(TimeControl::PerGame(_)) = self.time_control {
+            self.remaining.set(self.remaining.get().checked_sub(elapsed).unwrap_or_default());
+        }
+    }
+
+    /*
+     * Searches the position with Lazy SMP: every thread deepens its own search iteratively and
+     * they all share the transposition table, so that each thread reuses what the others found.
+     * Helper threads with an odd index search one ply deeper to spread the work. Only the
+     * iterations completed by the main thread decide the move, and the helpers are stopped as
+     * soon as it is done.
+     */
+    fn search(&self, pos: Position, start: Instant, budget: Option<Duration>) -> SearchResult {
+        let control = Control::new(budget.map(|budget| start + budget));
+        let max_depth = self.max_depth.saturating_add(1);
+        let moves = pos.legal_moves();
+
+        let mut result = SearchResult::new(moves & moves.wrapping_neg());
+
+        result.nodes = std::thread::scope(|scope| {
+            let mut helpers = vec![];
+
+            for id in 1..self.threads {
+                let (control, table) = (&control, &self.table);
+                let (ordering, evaluator) = (self.ordering, &self.evaluator);
+                helpers.push(scope.spawn(move || -> u64 {
+                    let mut searcher = Searcher::new(control, table, ordering, evaluator);
+                    let mut previous = None;
+                    for depth in (1 + (id & 1) as u8)..=max_depth {
+                        previous = Some(searcher.aspiration(pos, depth, previous).0);
+                        if control.is_stopped() {
+                            break;
+                        }
+                    }
+                    searcher.nodes
+                }));
+            }
+
+            let mut searcher =
+                Searcher::new(&control, &self.table, self.ordering, &self.evaluator);
+            let mut previous = None;
+            for depth in 1..=max_depth {
+                searcher.hit_horizon = false;
+                let (value, mv) = searcher.aspiration(pos, depth, previous);
+                if control.is_stopped() {
+                    break;
+                }
+                previous = Some(value);
+                result.best_move = mv;
+                result.score = value;
+                result.depth = depth;
+                result.pv = searcher.principal_variation(pos, depth);
+                // No need to go deeper once the whole game tree fits, and an iteration
+                // started past half the budget is unlikely to complete.
+                let late = budget.is_some_and(|budget| start.elapsed() * 2 > budget);
+                if !searcher.hit_horizon || late {
+                    break;
+                }
+            }
+
+            control.stop();
+            helpers.into_iter().map(|helper| helper.join().unwrap()).sum::<u64>() + searcher.nodes
+        });
+
+        result
+    }
+
+    /*
+     * Returns the score of a result in discs: exact scores as they are, wins, draws and losses as
+     * +64, 0 and -64, and heuristic scores converted from the units of the evaluator, unless they
+     * announce the end of the game, in which case the disc difference it ends with is given.
+     */
+    pub fn discs(&self, result: &SearchResult) -> f32 {
+        match result.kind {
+            ScoreKind::Heuristic if result.score.abs() > WIN_VALUE / 2 => {
+                (result.score - result.score.signum() * WIN_VALUE) as f32
+            },
+            ScoreKind::Heuristic => result.score as f32 / self.evaluator.disc_scale() as f32,
+            ScoreKind::Exact => result.score as f32,
+            ScoreKind::WinLossDraw => 64.0 * result.score.signum() as f32,
+        }
+    }
+
+    /*
+     * Analyzes every legal move of the position, returning one result per move ranked from best
+     * to worst, each with the exact score of the move and the principal variation starting with
+     * it. Positions with few enough empty squares are solved exactly, others are searched with
+     * iterative deepening up to the fixed depth of the AI or until its time budget runs out, in
+     * which case the results of the last iteration completed for all the moves are kept. Under a
+     * time control, a solve taking more than half the budget is given up for the search. The
+     * analysis runs on a single thread.
+     */
+    pub fn analyze(&self, pos: Position) -> Vec<SearchResult> {
+        let start = Instant::now();
+        let budget = self.budget(pos);
+        self.table.new_search();
+
+        let mut moves = pos.legal_moves();
+        let mut results: Vec<SearchResult> = vec![];
+
+        while moves != 0 {
+            results.push(SearchResult::new(moves.pop_lsb()));
+        }
+
+        // The solve may take half the budget, leaving the other half to the search it falls
+        // back to when it runs out of time.
+        let mut solved = false;
+        if pos.empties() <= self.endgame_empties {
+            let control = Control::new(budget.map(|budget| start + budget / 2));
+            let mut solver = EndgameSolver::new(&self.table, &control);
+            let mut exact = results.clone();
+
+            for result in &mut exact {
+                let child = pos.play(result.best_move);
+                let nodes = solver.nodes();
+                result.score = -solver.solve(child).0;
+                result.kind = ScoreKind::Exact;
+                result.depth = pos.empties();
+                result.pv.extend(solver.principal_variation(child));
+                result.nodes = solver.nodes() - nodes;
+                if control.is_stopped() {
+                    break;
+                }
+            }
+
+            if !control.is_stopped() {
+                results = exact;
+                solved = true;
+            }
+        }
+
+        if !solved {
+            let control = Control::new(budget.map(|budget| start + budget));
+            let mut searcher =
+                Searcher::new(&control, &self.table, self.ordering, &self.evaluator);
+
+            for depth in 1..=self.max_depth.saturating_add(1) {
+                let mut iteration = results.clone();
+                searcher.hit_horizon = false;
+
+                for result in &mut iteration {
+                    let child = pos.play(result.best_move);
+                    let previous = Some(-result.score).filter(|_| result.depth > 0);
+                    let nodes = searcher.nodes;
+                    result.score = -searcher.aspiration(child, depth - 1, previous).0;
+                    result.depth = depth;
+                    result.pv = std::iter::once(result.best_move)
+                        .chain(searcher.principal_variation(child, depth - 1))
+                        .collect();
+                    result.nodes += searcher.nodes - nodes;
+                    if control.is_stopped() {
+                        break;
+                    }
+                }
+
+                if control.is_stopped() {
+                    break;
+                }
+                iteration.sort_by_key(|result| -result.score);
+                results = iteration;
+                let late = budget.is_some_and(|budget| start.elapsed() * 2 > budget);
+                if !searcher.hit_horizon || late {
+                    break;
+                }
+            }
+        }
+
+        results.sort_by_key(|result| -result.score);
+        for result in &mut results {
+            result.elapsed = start.elapsed();
+        }
+        self.spend(start.elapsed());
+        results
+    }
+
+    /*
+     * Solves the position to the end, either exactly or only as won, drawn or lost. The score is
+     * the final disc difference, or its sign only. Returns None if the control stops the solve.
+     */
+    fn solve(&self, pos: Position, kind: ScoreKind, control: &Control) -> Option<SearchResult> {
+        let mut solver = EndgameSolver::new(&self.table, control);
+
+        let (score, best_move, pv) = if kind == ScoreKind::Exact {
+            let (score, best_move) = solver.solve(pos);
+            (score, best_move, solver.principal_variation(pos))
+        } else {
+            let (outcome, best_move) = solver.solve_wld(pos);
+            let score = match outcome {
+                Outcome::Win => 1,
+                Outcome::Draw => 0,
+                Outcome::Loss => -1,
+            };
+            (score, best_move, vec![best_move])
+        };
+
+        if control.is_stopped() {
+            return None;
+        }
+
+        Some(SearchResult {
+            best_move,
+            score,
+            kind,
+            depth: pos.empties(),
+            nodes: solver.nodes(),
+            elapsed: Duration::from_secs(0),
+            pv,
+        })
     }
 }
 
+//#################################################################################################
+//
+//                                     SEARCH CONTROL
+//
+//#################################################################################################
+
 /*
- * The evaluation function, using masks to do a weighted sum of the board and the move generation
- * algorithm to measure mobility for both players.
+ * The state shared by all the threads searching for a move, or by the endgame solver, telling
+ * them when to stop.
  */
-#[inline(always)]
-fn evaluate(oth: Othello) -> i32 {
-    let mut res: i32 = 0;
+pub struct Control {
+    deadline: Option<Instant>,
+    stopped: AtomicBool,
+}
 
-    let black: BitBoard = oth.get_bitboard(Color::Black);
-    let white: BitBoard = oth.get_bitboard(Color::White);
+impl Control {
+    /*
+     * Creates a new Control, with an optional deadline after which the search must stop.
+     */
+    pub fn new(deadline: Option<Instant>) -> Control {
+        Control { deadline, stopped: AtomicBool::new(false) }
+    }
 
-    macro_rules! delta_mask {
-        ($mask: expr, $val: expr) => {
-            res += $val * ((black & $mask).pop_cnt() as i32 - (white & $mask).pop_cnt() as i32);
+    /*
+     * Checks the clock and returns true if the search has to stop.
+     */
+    pub fn check(&self) -> bool {
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                self.stopped.store(true, Ordering::Relaxed);
+            }
         }
+        self.is_stopped()
     }
 
-    delta_mask!(0x8100000000000081, 100);
-    delta_mask!(0x2400810000810024, 10);
-    delta_mask!(0x1800008181000018, 5);
-    delta_mask!(0x00003C3C3C3C0000, -1);
-    delta_mask!(0x003C424242423C00, -2);
-    delta_mask!(0x4281000000008142, -20);
-    delta_mask!(0x0042000000004200, -50);
-
-    let black_mobility: i32 = oth.gen_moves(Color::Black).pop_cnt() as i32;
-    let white_mobility: i32 = oth.gen_moves(Color::White).pop_cnt() as i32;
-    res += 5 * (black_mobility - white_mobility);
+    /*
+     * Stops the search.
+     */
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
 
-    res
+    /*
+     * Returns true if the search has been stopped.
+     */
+    #[inline(always)]
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
 }
 
+//#################################################################################################
+//
+//                                        SEARCH
+//
+//#################################################################################################
+
 /*
- * Another evaluation function that specializes in ended games, returns the max value of i32 if
- * black wins, the min value if white wins or 0 if it's a draw. The 0 encourages the AI to
- * seek a draw if it's already loosing and can't manage to win.
+ * Another evaluation function that specializes in ended games, returns WIN_VALUE plus the disc
+ * difference if the color to move wins, minus WIN_VALUE plus the disc difference if it loses or 0
+ * if it's a draw. The 0 encourages the AI to seek a draw if it's already loosing and can't manage
+ * to win.
  */
 #[inline(always)]
-fn evaluate_end(oth: Othello) -> i32 {
-    let black_score = oth.get_bitboard(Color::Black).pop_cnt();
-    let white_score = oth.get_bitboard(Color::White).pop_cnt();
-
-    if black_score > white_score {
-        std::i32::MAX
-    } else if black_score < white_score {
-        std::i32::MIN
+fn evaluate_end(pos: Position) -> i32 {
+    let diff = final_score(pos.board(), pos.side_to_move());
+
+    if diff > 0 {
+        WIN_VALUE + diff
+    } else if diff < 0 {
+        -WIN_VALUE + diff
     } else {
         0
     }
 }
 
 /*
- * Standard recursive minimax with alpha-beta prunig algorithm.
+ * The state of a single searching thread: the shared control, transposition table and evaluation
+ * function, its own move ordering tables, the distance to the root of the current node, the
+ * triangular table of the principal variations found at each ply, a count of the visited nodes,
+ * used to check the clock every now and then, and whether the search was cut by the depth limit
+ * somewhere.
  */
-fn alphabeta(oth: Othello, mut alpha: i32, mut beta: i32, mut color: Color, mut depth: u8) -> i32 {
-    if depth == 0 {
-        return evaluate(oth);
+struct Searcher<'a> {
+    control: &'a Control,
+    table: &'a TranspositionTable,
+    evaluator: &'a Evaluator,
+    orderer: MoveOrderer,
+    ply: usize,
+    pv_table: Box<[[BitBoard; MAX_PLY]; MAX_PLY]>,
+    pv_len: [usize; MAX_PLY],
+    nodes: u64,
+    hit_horizon: bool,
+}
+
+impl<'a> Searcher<'a> {
+    /*
+     * Creates a new Searcher, obeying the given control, sharing the given table, ordering the
+     * moves according to the given policy and evaluating positions with the given evaluator.
+     */
+    fn new(
+        control: &'a Control,
+        table: &'a TranspositionTable,
+        ordering: MoveOrdering,
+        evaluator: &'a Evaluator,
+    ) -> Searcher<'a> {
+        Searcher {
+            control,
+            table,
+            evaluator,
+            orderer: MoveOrderer::new(ordering),
+            ply: 0,
+            pv_table: Box::new([[0; MAX_PLY]; MAX_PLY]),
+            pv_len: [0; MAX_PLY],
+            nodes: 0,
+            hit_horizon: false,
+        }
     }
 
-    let mut moves = oth.gen_moves(color);
+    /*
+     * Makes the given move, followed by the principal variation of the next ply, the principal
+     * variation of the current ply.
+     */
+    fn update_pv(&mut self, mv: BitBoard) {
+        let (ply, next_len) = (self.ply, self.pv_len[self.ply + 1]);
+        let (current, next) = self.pv_table.split_at_mut(ply + 1);
+        current[ply][0] = mv;
+        current[ply][1..=next_len].copy_from_slice(&next[0][..next_len]);
+        self.pv_len[ply] = next_len + 1;
+    }
 
-    if moves == 0 {
-        color = color.invert();
-        depth -= 1;
-        if depth == 0 {
-            return evaluate(oth);
+    /*
+     * Returns the principal variation found by the last search of the root position at the given
+     * depth, followed from the transposition table when it was cut short by a table hit.
+     */
+    fn principal_variation(&self, mut pos: Position, depth: u8) -> Vec<BitBoard> {
+        let mut pv: Vec<BitBoard> = self.pv_table[0][..self.pv_len[0]].to_vec();
+
+        for &mv in &pv {
+            pos = if mv == 0 { pos.pass() } else { pos.play(mv) };
+        }
+
+        while pv.len() < depth as usize {
+            let mv = match self.table.probe(pos.hash()) {
+                Some(entry) if entry.best_move & pos.legal_moves() != 0 => entry.best_move,
+                _ if pos.must_pass() => 0,
+                _ => break,
+            };
+            pos = if mv == 0 { pos.pass() } else { pos.play(mv) };
+            pv.push(mv);
         }
-        moves = oth.gen_moves(color);
-        if moves == 0 {
-            return evaluate_end(oth);
+
+        pv
+    }
+
+    /*
+     * Searches the root position at the given depth within an aspiration window centered on the
+     * value found by the previous iteration, widening the window each time the search falls
+     * outside of it. Won and lost positions are searched with a full window.
+     */
+    fn aspiration(&mut self, pos: Position, depth: u8, previous: Option<i32>) -> (i32, BitBoard) {
+        let mut delta = ASPIRATION_WINDOW;
+
+        let (mut alpha, mut beta) = match previous {
+            Some(value) if value.abs() < WIN_VALUE => (value - delta, value + delta),
+            _ => (-INFINITY, INFINITY),
+        };
+
+        loop {
+            let (value, mv) = self.alphabeta(pos, alpha, beta, depth);
+            if self.control.is_stopped() || (alpha < value && value < beta) {
+                return (value, mv);
+            }
+
+            delta *= 2;
+            if value <= alpha {
+                alpha = if delta < WIN_VALUE { value - delta } else { -INFINITY };
+            } else {
+                beta = if delta < WIN_VALUE { value + delta } else { INFINITY };
+            }
         }
     }
 
-    let mut value: i32;
-    let new_color = color.invert();
-    depth -= 1;
+    /*
+     * Recursive negamax with alpha-beta pruning, in its principal variation search form: once a
+     * first move has been searched with the full window, the other moves are only proven worse
+     * with a zero window, and searched again with the full window when that fails. Values are
+     * given from the point of view of the color to move.
+     * Returns 0 as soon as the search is stopped, in which case the result has to be discarded.
+     * Values and best moves are stored in the transposition table, and moves are tried in the
+     * order given by the move ordering policy. Returns the value of the position along with its
+     * best move.
+     */
+    fn alphabeta(
+        &mut self,
+        pos: Position,
+        mut alpha: i32,
+        mut beta: i32,
+        depth: u8,
+    ) -> (i32, BitBoard) {
+        self.pv_len[self.ply] = 0;
+        self.nodes += 1;
+        if self.nodes & 0x3FF == 0 && self.control.check() || self.control.is_stopped() {
+            return (0, 0);
+        }
+
+        if depth == 0 {
+            self.hit_horizon = true;
+            return (self.evaluator.evaluate(pos.board(), pos.side_to_move()), 0);
+        }
 
-    match color {
-        Color::Black => {
-            value = std::i32::MIN;
-            while moves != 0 {
-                let new_oth = oth.make_move(color, moves.pop_lsb());
-                value = std::cmp::max(value, alphabeta(new_oth, alpha, beta, new_color, depth));
-                alpha = std::cmp::max(alpha, value);
+        let hash = pos.hash();
+        let mut hash_move: BitBoard = 0;
+
+        if let Some(entry) = self.table.probe(hash) {
+            hash_move = entry.best_move;
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => { alpha = entry.value; beta = entry.value; },
+                    Bound::Lower => alpha = std::cmp::max(alpha, entry.value),
+                    Bound::Upper => beta = std::cmp::min(beta, entry.value),
+                }
                 if alpha >= beta {
-                    break;
+                    self.hit_horizon |= entry.depth != DEPTH_TO_END;
+                    return (entry.value, entry.best_move);
                 }
             }
-        },
-        Color::White => {
-            value = std::i32::MAX;
-            while moves != 0 {
-                let new_oth = oth.make_move(color, moves.pop_lsb());
-                value = std::cmp::min(value, alphabeta(new_oth, alpha, beta, new_color, depth));
-                beta = std::cmp::min(beta, value);
-                if alpha >= beta {
-                    break;
+        }
+
+        if pos.is_game_over() {
+            return (evaluate_end(pos), 0);
+        }
+        if pos.must_pass() {
+            self.ply += 1;
+            let value = -self.alphabeta(pos.pass(), -beta, -alpha, depth - 1).0;
+            self.ply -= 1;
+            self.update_pv(0);
+            return (value, 0);
+        }
+
+        let (alpha_orig, beta_orig) = (alpha, beta);
+        let outer_hit_horizon = std::mem::replace(&mut self.hit_horizon, false);
+
+        let mut value: i32 = -INFINITY;
+        let mut best_move: BitBoard = 0;
+
+        self.ply += 1;
+
+        for mv in self.orderer.order(pos, pos.legal_moves(), hash_move, self.ply - 1, depth) {
+            let new_pos = pos.play(mv);
+
+            let new_value = if best_move == 0 {
+                -self.alphabeta(new_pos, -beta, -alpha, depth - 1).0
+            } else {
+                let new_value = -self.alphabeta(new_pos, -alpha - 1, -alpha, depth - 1).0;
+                if alpha < new_value && new_value < beta {
+                    -self.alphabeta(new_pos, -beta, -new_value, depth - 1).0
+                } else {
+                    new_value
                 }
+            };
+
+            if new_value > value || best_move == 0 {
+                value = new_value;
+                best_move = mv;
             }
-        },
-    }
+            if new_value > alpha {
+                self.ply -= 1;
+                self.update_pv(mv);
+                self.ply += 1;
+            }
+            alpha = std::cmp::max(alpha, value);
+            if alpha >= beta {
+                self.orderer.cutoff(pos.side_to_move(), mv, self.ply - 1, depth);
+                break;
+            }
+        }
+
+        self.ply -= 1;
+
+        if !self.control.is_stopped() {
+            let bound = if value <= alpha_orig {
+                Bound::Upper
+            } else if value >= beta_orig {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            let depth = if self.hit_horizon { depth } else { DEPTH_TO_END };
+            self.table.store(hash, Entry { depth, bound, value, best_move });
+        }
 
-    value
+        self.hit_horizon |= outer_hit_horizon;
+        (value, best_move)
+    }
 }
 
 impl Player for AlphaBetaPlayer {
     /*
-     * Launches one thread per possible moves and wait for them to complete their tree search
-     * using the above alpha-beta algorithm and selects the best move, according to the color
-     * of the player.
+     * Chooses the best move found by AlphaBetaPlayer::think.
      */
-    fn chose_move(&self, oth: Othello, mut moves: BitBoard, color: Color) -> BitBoard {
-        let mut handles = vec![];
+    fn chose_move(&self, pos: Position) -> BitBoard {
+        self.think(pos).best_move
+    }
 
-        while moves != 0 {
-            let mv = moves.pop_lsb();
-            let oth = oth.make_move(color, mv);
-            let color = color.invert();
-            let depth = self.max_depth;
-            handles.push(std::thread::spawn(move || -> (i32, BitBoard) {
-                (alphabeta(oth, std::i32::MIN, std::i32::MAX, color, depth), mv)
-            }));
+    /*
+     * Plays from the opening book while the position is in it. Then solves the game perfectly
+     * when few enough squares are empty, or plays a winning or drawing move found by a
+     * win/loss/draw solve a bit earlier. Otherwise, deepens the search one ply
+     * at a time up to the fixed depth of the AI or, when it has a time control, until the budget
+     * for the move runs out, and reports the result of the last completed iteration. Under a
+     * time control, a solve taking more than half the budget is given up for the search.
+     */
+    fn think(&self, pos: Position) -> SearchResult {
+        let start = Instant::now();
+
+        if let Some(book) = &self.book {
+            let choice = book.choose(pos, self.book_randomness, &mut self.rng.borrow_mut());
+            if let Some((mv, score)) = choice {
+                let mut result = SearchResult::new(mv);
+                // Back from discs to the units of the evaluator, as for a heuristic score.
+                result.score = (score * self.evaluator.disc_scale() as f32).round() as i32;
+                result.elapsed = start.elapsed();
+                return result;
+            }
+        }
+
+        let budget = self.budget(pos);
+        self.table.new_search();
+
+        let empties = pos.empties();
+        let control = Control::new(budget.map(|budget| start + budget / 2));
+        let mut result = None;
+
+        if empties <= self.endgame_empties {
+            result = self.solve(pos, ScoreKind::Exact, &control);
+        } else if empties <= self.wld_empties {
+            result = self.solve(pos, ScoreKind::WinLossDraw, &control)
+                .filter(|result| result.score >= 0);
+        }
+
+        let mut result = result.unwrap_or_else(|| {
+            if pos.legal_moves().pop_cnt() > 1 {
+                self.search(pos, start, budget)
+            } else {
+                SearchResult::new(pos.legal_moves())
+            }
+        });
+
+        result.elapsed = start.elapsed();
+        self.spend(result.elapsed);
+        result
+    }
+}
+
+//#################################################################################################
+//
+//                                     SEARCH TEST
+//
+//#################################################################################################
+
+/*
+ * Checks the negamax principal variation search against the plain minimax it replaced.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::*;
+
+    /*
+     * The former minimax with alpha-beta pruning, without transposition table, with the values
+     * given from Black's point of view.
+     */
+    fn minimax(mut pos: Position, mut alpha: i32, mut beta: i32, mut depth: u8) -> i32 {
+        if depth == 0 {
+            return Evaluator::Masks.evaluate(pos.board(), Color::Black);
+        }
+
+        if pos.is_game_over() {
+            return evaluate_end(Position::create(pos.board(), Color::Black));
+        }
+        if pos.must_pass() {
+            pos = pos.pass();
+            depth -= 1;
+            if depth == 0 {
+                return Evaluator::Masks.evaluate(pos.board(), Color::Black);
+            }
         }
 
-        let mut res: (i32, BitBoard) = handles.remove(0).join().unwrap();
-        let mut val: i32 = res.0;
-        let mut best: BitBoard = res.1;
+        let mut moves = pos.legal_moves();
+        let mut value: i32;
 
-        match color {
+        match pos.side_to_move() {
             Color::Black => {
-                for handle in handles {
-                    res = handle.join().unwrap();
-                    if res.0 > val {
-                        val = res.0;
-                        best = res.1;
-                    }
+                value = -INFINITY;
+                while moves != 0 {
+                    let child = minimax(pos.play(moves.pop_lsb()), alpha, beta, depth - 1);
+                    value = std::cmp::max(value, child);
+                    alpha = std::cmp::max(alpha, value);
+                    if alpha >= beta { break; }
                 }
             },
             Color::White => {
-                for handle in handles {
-                    res = handle.join().unwrap();
-                    if res.0 < val {
-                        val = res.0;
-                        best = res.1;
-                    }
+                value = INFINITY;
+                while moves != 0 {
+                    let child = minimax(pos.play(moves.pop_lsb()), alpha, beta, depth - 1);
+                    value = std::cmp::min(value, child);
+                    beta = std::cmp::min(beta, value);
+                    if alpha >= beta { break; }
                 }
             },
         }
 
-        best
+        value
+    }
+
+    #[test]
+    fn scores_in_discs() {
+        let ai = AlphaBetaPlayer::new(1);
+        let result = |score, kind| SearchResult { score, kind, ..SearchResult::new(0) };
+
+        let three = 3 * Evaluator::Masks.disc_scale();
+        assert_eq!(ai.discs(&result(three, ScoreKind::Heuristic)), 3.0);
+        assert_eq!(ai.discs(&result(-WIN_VALUE - 12, ScoreKind::Heuristic)), -12.0);
+        assert_eq!(ai.discs(&result(-6, ScoreKind::Exact)), -6.0);
+        assert_eq!(ai.discs(&result(1, ScoreKind::WinLossDraw)), 64.0);
+        assert_eq!(ai.discs(&result(0, ScoreKind::WinLossDraw)), 0.0);
+        assert_eq!(ai.discs(&result(-1, ScoreKind::WinLossDraw)), -64.0);
+    }
+
+    #[test]
+    fn solve_within_budget() {
+        // Solving 50 empty squares would take ages, so the AI has to fall back to the search.
+        let pos = Game::from_transcript("f5d6c3d3c4f4f6f3e6e7").unwrap().position();
+        let budget = Duration::from_millis(200);
+        let ai = AlphaBetaPlayer::with_time(TimeControl::PerMove(budget))
+            .with_endgame(60)
+            .with_threads(1)
+            .with_hash_size(1);
+
+        let result = ai.think(pos);
+        assert!(result.elapsed < 2 * budget, "think took {:?}", result.elapsed);
+        assert!(result.kind == ScoreKind::Heuristic);
+        assert!(pos.legal_moves() & result.best_move != 0);
+
+        let results = ai.analyze(pos);
+        assert!(results[0].elapsed < 2 * budget, "analyze took {:?}", results[0].elapsed);
+        assert!(results.iter().all(|result| result.kind == ScoreKind::Heuristic));
+    }
+
+    #[test]
+    fn same_values_as_minimax() {
+        let control = Control::new(None);
+        let mut pos = Position::new();
+
+        for _ in 0..12 {
+            for depth in 1..=5 {
+                let table = TranspositionTable::new(1);
+                let ordering = MoveOrdering::default();
+                let mut searcher = Searcher::new(&control, &table, ordering, &Evaluator::Masks);
+                let (value, mv) = searcher.alphabeta(pos, -INFINITY, INFINITY, depth);
+
+                let sign = if pos.side_to_move() == Color::Black { 1 } else { -1 };
+                let expected = sign * minimax(pos, -INFINITY, INFINITY, depth);
+                assert_eq!(value, expected, "Wrong value at depth {}", depth);
+                let reached = sign * minimax(pos.play(mv), -INFINITY, INFINITY, depth - 1);
+                assert_eq!(value, reached, "Wrong move at depth {}", depth);
+            }
+
+            let mut moves = pos.legal_moves();
+            for _ in 0..moves.pop_cnt() / 2 {
+                moves.pop_lsb();
+            }
+            pos = pos.play(moves.pop_lsb());
+        }
     }
 }
@@ -0,0 +1,238 @@
+use crate::types::*;
+use crate::position::*;
+use crate::alphabeta::*;
+use crate::files::*;
+
+use std::io::BufRead;
+use std::time::Duration;
+
+//#################################################################################################
+//
+//                                      BENCHMARK
+//
+//#################################################################################################
+
+/*
+ * The number of plies played from the starting position to reach each benchmark position.
+ */
+const PLIES: [usize; 10] = [8, 10, 12, 14, 16, 18, 20, 22, 24, 26];
+
+/*
+ * Generates the benchmark positions by playing pseudo-random, but always the same, games from the
+ * starting position.
+ */
+fn positions() -> Vec<Position> {
+    let mut rng = Rng::new(0x2545F4914F6CDD1D);
+
+    PLIES.iter().map(|&plies| {
+        let mut pos = Position::new();
+
+        for _ in 0..plies {
+            if pos.must_pass() {
+                pos = pos.pass();
+            }
+            pos = pos.play(rng.pick(pos.legal_moves()));
+        }
+
+        pos
+    }).collect()
+}
+
+/*
+ * Times a search of the position by the given AI, returning the time spent and the number of
+ * nodes visited.
+ */
+fn time_search(ai: &AlphaBetaPlayer, pos: Position) -> (Duration, u64) {
+    let result = ai.think(pos);
+    (result.elapsed, result.nodes)
+}
+
+/*
+ * Searches every benchmark position with a fresh AI created from each of the given descriptions,
+ * and prints the times and node counts, along with the speedup of the last AI over the first.
+ */
+pub fn run_bench(specs: &[String], create: impl Fn(&str) -> AlphaBetaPlayer) {
+    let mut totals: Vec<(Duration, u64)> = vec![(Duration::from_secs(0), 0); specs.len()];
+
+    println!("Searching {} positions with:", PLIES.len());
+    for (i, spec) in specs.iter().enumerate() {
+        println!("    ai {}: {}", i + 1, spec);
+    }
+
+    print!("{:>8}", "position");
+    for i in 0..specs.len() {
+        print!(" {:>10} {:>12}", format!("ai {} time", i + 1), "nodes");
+    }
+    println!(" {:>8}", "speedup");
+
+    let print_row = |name: &str, results: &[(Duration, u64)]| {
+        print!("{:>8}", name);
+        for (time, nodes) in results {
+            print!(" {:>9.3}s {:>12}", time.as_secs_f64(), nodes);
+        }
+        let speedup = results[0].0.as_secs_f64() / results[results.len() - 1].0.as_secs_f64();
+        println!(" {:>7.2}x", speedup);
+    };
+
+    for (i, pos) in positions().into_iter().enumerate() {
+        let results: Vec<(Duration, u64)> =
+            specs.iter().map(|spec| time_search(&create(spec), pos)).collect();
+        for (total, (time, nodes)) in totals.iter_mut().zip(&results) {
+            total.0 += *time;
+            total.1 += nodes;
+        }
+        print_row(&(i + 1).to_string(), &results);
+    }
+
+    print_row("total", &totals);
+}
+
+//#################################################################################################
+//
+//                                   ENDGAME TEST SUITE
+//
+//#################################################################################################
+
+/*
+ * A position of an endgame test suite, with its exact score and the moves reaching it.
+ */
+struct EndgameTest {
+    pos: Position,
+    score: i32,
+    best_moves: BitBoard,
+}
+
+/*
+ * Parses a line of an endgame test suite in Edax's OBF format, such as "<position> X; G8:+18;
+ * H1:+12;": the position followed by some of its moves with their exact scores, best moves
+ * first. Only the moves with the best score are kept.
+ */
+fn parse_endgame_test(line: &str) -> Result<EndgameTest, String> {
+    let mut fields = line.split(';').map(str::trim).filter(|field| !field.is_empty());
+    let pos: Position = fields.next().unwrap_or("").parse()
+        .map_err(|err: ParsePositionError| err.to_string())?;
+
+    let mut moves: Vec<(BitBoard, i32)> = vec![];
+    for field in fields {
+        let (mv, score) = field.split_once(':')
+            .ok_or_else(|| format!("invalid move and score \"{}\"", field))?;
+        let mv: Move = mv.parse().map_err(|err: ParseMoveError| err.to_string())?;
+        let score = score.trim();
+        let score: i32 = score.parse().map_err(|_| format!("invalid score \"{}\"", score))?;
+        moves.push((mv.into(), score));
+    }
+
+    let score = moves.iter().map(|&(_, score)| score).max().ok_or("no move and score")?;
+    let best_moves = moves.iter()
+        .filter(|&&(_, s)| s == score)
+        .fold(0, |best, &(mv, _)| best | mv);
+    Ok(EndgameTest { pos, score, best_moves })
+}
+
+/*
+ * Reads an endgame test suite, such as the FFO positions #40 to #59, from a file holding one
+ * position per line. Empty lines and lines starting with '%' or '#' are skipped.
+ */
+fn read_endgame_tests(path: &str) -> std::io::Result<Vec<EndgameTest>> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut tests: Vec<EndgameTest> = vec![];
+
+    for (i, line) in file.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with(['%', '#']) {
+            continue;
+        }
+        let test = parse_endgame_test(&line)
+            .map_err(|msg| invalid_data(format!("line {}: {}", i + 1, msg)))?;
+        tests.push(test);
+    }
+
+    Ok(tests)
+}
+
+/*
+ * Solves every position of the endgame test suite in the given file with the given AI, which
+ * must solve them exactly, and checks the score and move found against the known ones. Prints
+ * the nodes, time and nodes per second for every position and in total. Returns whether every
+ * position was solved correctly.
+ */
+pub fn run_endgame_suite(path: &str, ai: &AlphaBetaPlayer) -> std::io::Result<bool> {
+    let tests = read_endgame_tests(path)?;
+    let (mut total_time, mut total_nodes, mut correct) = (Duration::from_secs(0), 0, 0);
+
+    let nps = |time: Duration, nodes: u64| (nodes as f64 / time.as_secs_f64().max(1e-9)) as u64;
+
+    println!("Solving {} positions from {}:", tests.len(), path);
+    println!(
+        "{:>8} {:>7} {:>6} {:>6} {:>8} {:>14} {:>10} {:>12}  result",
+        "position", "empties", "move", "score", "expected", "nodes", "time", "nps",
+    );
+
+    for (i, test) in tests.iter().enumerate() {
+        let result = ai.think(test.pos);
+        let solved = result.kind == ScoreKind::Exact;
+        let ok = solved && result.score == test.score && result.best_move & test.best_moves != 0;
+        if ok {
+            correct += 1;
+        }
+        total_time += result.elapsed;
+        total_nodes += result.nodes;
+
+        println!(
+            "{:>8} {:>7} {:>6} {:>+6} {:>+8} {:>14} {:>9.3}s {:>12}  {}",
+            i + 1, test.pos.empties(), Move::from(result.best_move).to_string(),
+            result.score, test.score,
+            result.nodes, result.elapsed.as_secs_f64(), nps(result.elapsed, result.nodes),
+            if ok { "ok" } else if !solved { "NOT SOLVED" } else { "WRONG" },
+        );
+    }
+
+    println!(
+        "{:>8} {:>7} {:>6} {:>6} {:>8} {:>14} {:>9.3}s {:>12}  {}/{} correct",
+        "total", "", "", "", "",
+        total_nodes, total_time.as_secs_f64(), nps(total_time, total_nodes), correct, tests.len(),
+    );
+
+    Ok(correct == tests.len())
+}
+
+//#################################################################################################
+//
+//                                      BENCH TESTS
+//
+//#################################################################################################
+
+/*
+ * Checks the parsing of endgame test suite lines and the solving of a position of the FFO suite.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endgame_test_line() {
+        let start = "---------------------------OX------XO--------------------------- X;";
+        let test = parse_endgame_test(&format!("{} F5:+2; d3:+2; C4:-1;", start)).unwrap();
+        assert_eq!(test.pos, Position::new());
+        assert_eq!(test.score, 2);
+        assert_eq!(test.best_moves, 1 << 37 | 1 << 19);
+
+        assert!(parse_endgame_test(start).is_err());
+        assert!(parse_endgame_test(&format!("{} F5+2", start)).is_err());
+        assert!(parse_endgame_test(&format!("{} F5:two", start)).is_err());
+    }
+
+    /*
+     * FFO position #40, with 20 empty squares, which Black wins by 38 discs with A2 only.
+     */
+    #[test]
+    fn ffo_40() {
+        let line = "O--OOOOX-OOOOOOXOOXXOOOXOOXOOOXXOOOOOOXX---OOOOX----O--X-------- X; A2:+38;";
+        let test = parse_endgame_test(line).unwrap();
+        let result = AlphaBetaPlayer::new(60).with_endgame(60).think(test.pos);
+
+        assert!(result.kind == ScoreKind::Exact);
+        assert_eq!(result.score, 38);
+        assert_eq!(Move::from(result.best_move).to_string(), "a2");
+    }
+}
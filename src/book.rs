@@ -0,0 +1,335 @@
+use crate::types::*;
+use crate::othello::*;
+use crate::position::*;
+use crate::files::*;
+use crate::eval::*;
+use crate::alphabeta::*;
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+//#################################################################################################
+//
+//                                       CONSTANTS
+//
+//#################################################################################################
+
+/*
+ * The magic bytes and version at the start of a book file.
+ */
+const MAGIC: &[u8; 4] = b"OTHB";
+const VERSION: u32 = 1;
+
+/*
+ * Returns the key of a book position, the position with its canonical board, along with the
+ * symmetry turning the board into the canonical one.
+ */
+fn book_key(pos: Position) -> (Position, Symmetry) {
+    let (board, symmetry) = pos.board().canonical();
+    (Position::create(board, pos.side_to_move()), symmetry)
+}
+
+//#################################################################################################
+//
+//                                       BOOK TYPE
+//
+//#################################################################################################
+
+/*
+ * A move of a book position, as the square it is played on in the symmetry of the key, along
+ * with its score from the point of view of the color to move, in units of 1 / DISC_SCALE disc.
+ */
+#[derive(Clone, Copy)]
+struct BookMove {
+    square: u8,
+    score: i16,
+}
+
+/*
+ * An opening book, holding scored moves for positions reduced to their canonical board so that a
+ * single entry covers the eight symmetric positions.
+ */
+pub struct Book {
+    entries: HashMap<Position, Vec<BookMove>>,
+}
+
+impl Book {
+    /*
+     * Creates a new empty Book.
+     */
+    pub fn new() -> Book {
+        Book { entries: HashMap::new() }
+    }
+
+    /*
+     * Returns the number of positions in the book.
+     */
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /*
+     * Reads a book from a book file: the magic bytes "OTHB", the version and the number of
+     * positions as little-endian u32, then for each position the black and white boards of its
+     * key as little-endian u64, the color to move (0 for Black, 1 for White) and the number of
+     * moves as bytes, and for each move its square as a byte and its score as a little-endian i16.
+     */
+    pub fn read(mut reader: impl Read) -> std::io::Result<Book> {
+        let count = read_header(&mut reader, MAGIC, VERSION, "book")?;
+
+        let mut book = Book::new();
+
+        for _ in 0..count {
+            let mut position = [0u8; 18];
+            reader.read_exact(&mut position)?;
+            let black = u64::from_le_bytes(position[0..8].try_into().unwrap());
+            let white = u64::from_le_bytes(position[8..16].try_into().unwrap());
+            if black & white != 0 {
+                return Err(invalid_data("overlapping discs in book file"));
+            }
+            let color = match position[16] {
+                0 => Color::Black,
+                1 => Color::White,
+                _ => return Err(invalid_data("invalid color in book file")),
+            };
+
+            let mut moves: Vec<BookMove> = vec![];
+            for _ in 0..position[17] {
+                let mut mv = [0u8; 3];
+                reader.read_exact(&mut mv)?;
+                if mv[0] >= 64 {
+                    return Err(invalid_data("invalid square in book file"));
+                }
+                moves.push(BookMove { square: mv[0], score: i16::from_le_bytes([mv[1], mv[2]]) });
+            }
+
+            book.entries.insert(Position::create(Othello::create(black, white), color), moves);
+        }
+
+        Ok(book)
+    }
+
+    /*
+     * Loads a book from the book file at the given path.
+     */
+    pub fn load(path: &str) -> std::io::Result<Book> {
+        Book::read(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /*
+     * Writes the book in the format read by Book::read, positions being sorted so that the same
+     * book always gives the same file.
+     */
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        let mut keys: Vec<&Position> = self.entries.keys().collect();
+        keys.sort_by_key(|key| {
+            let (board, color) = (key.board(), key.side_to_move());
+            (board.get_bitboard(Color::Black), board.get_bitboard(Color::White), color)
+        });
+
+        write_header(&mut writer, MAGIC, VERSION, keys.len() as u32)?;
+
+        for key in keys {
+            let moves = &self.entries[key];
+            writer.write_all(&key.board().get_bitboard(Color::Black).to_le_bytes())?;
+            writer.write_all(&key.board().get_bitboard(Color::White).to_le_bytes())?;
+            writer.write_all(&[key.side_to_move() as u8, moves.len() as u8])?;
+            for mv in moves {
+                writer.write_all(&[mv.square])?;
+                writer.write_all(&mv.score.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /*
+     * Saves the book to a book file at the given path.
+     */
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        self.write(std::io::BufWriter::new(std::fs::File::create(path)?))
+    }
+
+    /*
+     * Returns the moves of the position if it is in the book, mapped back from the symmetry of
+     * its key, with their scores in discs.
+     */
+    pub fn probe(&self, pos: Position) -> Option<Vec<(BitBoard, f32)>> {
+        let (key, symmetry) = book_key(pos);
+        let inverse = symmetry.inverse();
+        let moves = pos.legal_moves();
+
+        let scored: Vec<(BitBoard, f32)> = self.entries.get(&key)?.iter()
+            .map(|book_move| {
+                let mv = (1u64 << book_move.square).transform(inverse);
+                (mv, book_move.score as f32 / DISC_SCALE as f32)
+            })
+            .filter(|&(mv, _)| moves & mv != 0)
+            .collect();
+
+        Some(scored).filter(|scored| !scored.is_empty())
+    }
+
+    /*
+     * Chooses a move of the position if it is in the book, at random among the moves scoring at
+     * most the given number of discs below the best one. Returns the move along with its score.
+     */
+    pub fn choose(
+        &self,
+        pos: Position,
+        randomness: f32,
+        rng: &mut Rng,
+    ) -> Option<(BitBoard, f32)> {
+        let moves = self.probe(pos)?;
+        let best = moves.iter().map(|&(_, score)| score).fold(f32::MIN, f32::max);
+        let candidates: Vec<(BitBoard, f32)> = moves.into_iter()
+            .filter(|&(_, score)| score >= best - randomness)
+            .collect();
+        Some(candidates[(rng.next_u64() % candidates.len() as u64) as usize])
+    }
+
+    /*
+     * Adds the moves of the position, scored in discs, to the book, replacing any previous entry.
+     */
+    fn insert(&mut self, pos: Position, moves: &[(BitBoard, f32)]) {
+        let (key, symmetry) = book_key(pos);
+
+        let book_moves = moves.iter().map(|&(mv, score)| BookMove {
+            square: mv.transform(symmetry).trailing_zeros() as u8,
+            score: (score * DISC_SCALE as f32)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32) as i16,
+        }).collect();
+
+        self.entries.insert(key, book_moves);
+    }
+
+    /*
+     * Adds the analysis by the given AI of the position to the book, returning its moves with
+     * their scores converted to discs, whether they are heuristic, exact or announce the end of
+     * the game.
+     */
+    fn analyze(&mut self, ai: &AlphaBetaPlayer, pos: Position) -> Vec<(BitBoard, f32)> {
+        let moves: Vec<(BitBoard, f32)> = ai.analyze(pos).iter()
+            .map(|result| (result.best_move, ai.discs(result)))
+            .collect();
+        self.insert(pos, &moves);
+        moves
+    }
+
+    /*
+     * Expands the book with the analysis by the given AI of the given positions, such as those of
+     * recorded games, that are within the given number of plies from the starting position and
+     * not in the book yet.
+     */
+    pub fn add_positions(
+        &mut self,
+        ai: &AlphaBetaPlayer,
+        positions: impl Iterator<Item = Position>,
+        plies: usize,
+    ) {
+        for pos in positions {
+            let in_range = pos.empties() as usize + plies > 60 && pos.legal_moves() != 0;
+            if in_range && self.probe(pos).is_none() {
+                self.analyze(ai, pos);
+                println!("ply {:>2}: {} positions", 60 - pos.empties(), self.len());
+            }
+        }
+    }
+
+    /*
+     * Expands the book with the analysis by the given AI of every position reached from the
+     * starting position within the given number of plies, following only the moves scoring at
+     * most the given number of discs below the best one. Positions already in the book are not
+     * analyzed again.
+     */
+    pub fn build(&mut self, ai: &AlphaBetaPlayer, plies: usize, window: f32) {
+        let mut visited: HashSet<Position> = HashSet::new();
+        let mut stack: Vec<(Position, usize)> = vec![(Position::new(), 0)];
+
+        while let Some((mut pos, ply)) = stack.pop() {
+            if pos.must_pass() {
+                pos = pos.pass();
+            }
+            if pos.is_game_over() || ply >= plies || !visited.insert(book_key(pos).0) {
+                continue;
+            }
+
+            let moves = match self.probe(pos) {
+                Some(moves) => moves,
+                None => {
+                    let moves = self.analyze(ai, pos);
+                    println!("ply {:>2}: {} positions", ply, self.len());
+                    moves
+                },
+            };
+
+            let best = moves.iter().map(|&(_, score)| score).fold(f32::MIN, f32::max);
+            for &(mv, score) in moves.iter().rev() {
+                if score >= best - window {
+                    stack.push((pos.play(mv), ply + 1));
+                }
+            }
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                      BOOK TESTS
+//
+//#################################################################################################
+
+/*
+ * Checks that a book entry covers the symmetric positions and survives a round trip to a file.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric_probe() {
+        let pos = Position::new().play(1 << 37);
+        let mut moves = pos.legal_moves();
+        let mut scored: Vec<(BitBoard, f32)> = vec![];
+        while moves != 0 {
+            scored.push((moves.pop_lsb(), scored.len() as f32 - 1.5));
+        }
+
+        let mut book = Book::new();
+        book.insert(pos, &scored);
+
+        let mut file: Vec<u8> = vec![];
+        book.write(&mut file).unwrap();
+        let book = Book::read(file.as_slice()).unwrap();
+
+        assert_eq!(book.probe(pos).unwrap(), scored);
+
+        // The four first moves lead to symmetric positions.
+        let mut first_moves = Position::new().legal_moves();
+        while first_moves != 0 {
+            let symmetric = Position::new().play(first_moves.pop_lsb());
+            let mut probed = book.probe(symmetric).unwrap();
+            assert!(probed.iter().all(|&(mv, _)| symmetric.legal_moves() & mv != 0));
+            probed.sort_by(|a, b| a.1.total_cmp(&b.1));
+            assert_eq!(probed.len(), scored.len());
+            assert!(probed.iter().zip(&scored).all(|(a, b)| a.1 == b.1));
+        }
+        assert!(book.probe(Position::new()).is_none());
+    }
+
+    #[test]
+    fn positions_from_games() {
+        let ai = AlphaBetaPlayer::new(1).with_threads(1).with_hash_size(1);
+        let game = crate::game::Game::from_transcript("f5d6c3").unwrap();
+
+        // Only the positions before the second ply are added, once.
+        let mut book = Book::new();
+        book.add_positions(&ai, game.positions().iter().copied(), 2);
+        book.add_positions(&ai, game.positions().iter().copied(), 2);
+        assert_eq!(book.len(), 2);
+        assert!(book.probe(game.positions()[1]).is_some());
+        assert!(book.probe(game.positions()[2]).is_none());
+    }
+}
@@ -0,0 +1,467 @@
+use crate::types::*;
+use crate::othello::*;
+use crate::position::*;
+use crate::transposition::*;
+use crate::alphabeta::Control;
+
+//#################################################################################################
+//
+//                                       CONSTANTS
+//
+//#################################################################################################
+
+/*
+ * Xored into the hash of positions stored by the solver, so that its entries, which hold exact
+ * disc differences, never get mixed up with those of the midgame search in a shared table.
+ */
+const ENDGAME_KEY: u64 = 0x5DEECE66DA3F1C27;
+
+/*
+ * Below this number of empty squares, moves are ordered by parity alone, as sorting them by
+ * mobility costs more than it saves.
+ */
+const FASTEST_FIRST_EMPTIES: u8 = 7;
+
+/*
+ * Below this number of empty squares, positions are not stored in the transposition table.
+ */
+const TABLE_EMPTIES: u8 = 8;
+
+/*
+ * The four quadrants of the board, used to compute the parity of the regions of empty squares.
+ */
+const QUADRANTS: [BitBoard; 4] = [
+    0x000000000F0F0F0F, 0x00000000F0F0F0F0,
+    0x0F0F0F0F00000000, 0xF0F0F0F000000000,
+];
+
+//#################################################################################################
+//
+//                                      FINAL SCORE
+//
+//#################################################################################################
+
+/*
+ * Returns the final disc difference of a finished game from the point of view of the given color,
+ * with the empty squares going to the winner.
+ */
+pub fn final_score(oth: Othello, color: Color) -> i32 {
+    let own = oth.get_bitboard(color).pop_cnt() as i32;
+    let opp = oth.get_bitboard(color.invert()).pop_cnt() as i32;
+    let empties = 64 - own - opp;
+
+    if own > opp {
+        own - opp + empties
+    } else if own < opp {
+        own - opp - empties
+    } else {
+        0
+    }
+}
+
+/*
+ * Returns the BitBoard of the empty squares lying in a quadrant with an odd number of empty
+ * squares. Playing there first tends to give the last move of each region to the side to move.
+ */
+#[inline(always)]
+fn odd_squares(empties: BitBoard) -> BitBoard {
+    QUADRANTS.iter()
+        .filter(|&&quadrant| (empties & quadrant).pop_cnt() & 1 == 1)
+        .fold(0, |odd, quadrant| odd | (empties & quadrant))
+}
+
+//#################################################################################################
+//
+//                                      OUTCOME TYPE
+//
+//#################################################################################################
+
+/*
+ * The Outcome type, the result of a game from the point of view of one of the players.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Win, Draw, Loss,
+}
+
+impl Outcome {
+    /*
+     * Returns the outcome matching the sign of a final disc difference.
+     */
+    pub fn from_score(score: i32) -> Outcome {
+        match score.signum() {
+            1 => Outcome::Win,
+            0 => Outcome::Draw,
+            _ => Outcome::Loss,
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                   ENDGAME SOLVER TYPE
+//
+//#################################################################################################
+
+/*
+ * A perfect-play solver, searching positions until the end of the game to find their exact
+ * final disc difference.
+ */
+pub struct EndgameSolver<'a> {
+    table: &'a TranspositionTable,
+    control: &'a Control,
+    nodes: u64,
+}
+
+impl<'a> EndgameSolver<'a> {
+    /*
+     * Creates a new EndgameSolver, storing the positions it solves in the given table, and
+     * stopping when the given control tells it to.
+     */
+    pub fn new(table: &'a TranspositionTable, control: &'a Control) -> EndgameSolver<'a> {
+        EndgameSolver { table, control, nodes: 0 }
+    }
+
+    /*
+     * Solves the position. Returns the final disc difference for the color to move under perfect
+     * play, along with a move reaching it (0 if the color has to pass or the game is over). Once
+     * the solver has been stopped, the result has to be discarded.
+     */
+    pub fn solve(&mut self, pos: Position) -> (i32, BitBoard) {
+        self.solve_window(pos, -64, 64)
+    }
+
+    /*
+     * Returns the line perfect play follows from the position, found by solving every position
+     * along it, which is cheap once the table has been filled by a first solve. Passes are
+     * recorded as 0. The line is cut short if the solver is stopped.
+     */
+    pub fn principal_variation(&mut self, mut pos: Position) -> Vec<BitBoard> {
+        let mut pv: Vec<BitBoard> = vec![];
+
+        while !pos.is_game_over() && !self.control.is_stopped() {
+            if pos.must_pass() {
+                pv.push(0);
+                pos = pos.pass();
+            } else {
+                let (_, mv) = self.solve(pos);
+                pv.push(mv);
+                pos = pos.play(mv);
+            }
+        }
+
+        pv
+    }
+
+    /*
+     * Returns the number of nodes visited by the solver so far.
+     */
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /*
+     * Solves the position, only telling whether it is won, drawn or lost for the color to move,
+     * which is much cheaper than finding the exact score. Also returns a move reaching that
+     * outcome (0 if the color has to pass or the game is over).
+     */
+    pub fn solve_wld(&mut self, pos: Position) -> (Outcome, BitBoard) {
+        let (score, mv) = self.solve_window(pos, -1, 1);
+        (Outcome::from_score(score), mv)
+    }
+
+    /*
+     * Solves the position at the root within the (alpha, beta) window, returning the best move
+     * found along with its score.
+     */
+    fn solve_window(&mut self, pos: Position, mut alpha: i32, beta: i32) -> (i32, BitBoard) {
+        let (oth, color) = (pos.board(), pos.side_to_move());
+        let moves = pos.legal_moves();
+
+        if moves == 0 {
+            return (self.negamax(oth, color, alpha, beta), 0);
+        }
+
+        let mut best: (i32, BitBoard) = (i32::MIN, 0);
+
+        for mv in self.order_moves(oth, color, moves, 0) {
+            let score = -self.negamax(oth.make_move(color, mv), color.invert(), -beta, -alpha);
+            if score > best.0 {
+                best = (score, mv);
+                alpha = std::cmp::max(alpha, score);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        best
+    }
+
+    /*
+     * The fail-soft negamax search at the heart of the solver, dispatching to the specialized
+     * code for the last four empty squares. Returns 0 as soon as the solver is stopped.
+     */
+    fn negamax(&mut self, oth: Othello, color: Color, mut alpha: i32, mut beta: i32) -> i32 {
+        let empties: BitBoard = oth.empty_squares();
+        let n_empties = empties.pop_cnt();
+
+        match n_empties {
+            0 => { self.nodes += 1; return final_score(oth, color); },
+            1 => return self.solve_last(oth, color, empties),
+            2..=4 => return self.solve_shallow(oth, color, alpha, beta, empties),
+            _ => (),
+        }
+
+        self.nodes += 1;
+        // The clock is only checked high enough in the tree for its cost not to matter.
+        if n_empties >= TABLE_EMPTIES && self.control.check() || self.control.is_stopped() {
+            return 0;
+        }
+
+        let moves = oth.gen_moves(color);
+
+        if moves == 0 {
+            if oth.gen_moves(color.invert()) == 0 {
+                return final_score(oth, color);
+            }
+            return -self.negamax(oth, color.invert(), -beta, -alpha);
+        }
+
+        let use_table = n_empties >= TABLE_EMPTIES;
+        let hash = oth.hash(color) ^ ENDGAME_KEY;
+        let mut hash_move: BitBoard = 0;
+
+        if use_table {
+            if let Some(entry) = self.table.probe(hash) {
+                hash_move = entry.best_move & moves;
+                if entry.depth >= n_empties {
+                    match entry.bound {
+                        Bound::Exact => return entry.value,
+                        Bound::Lower => alpha = std::cmp::max(alpha, entry.value),
+                        Bound::Upper => beta = std::cmp::min(beta, entry.value),
+                    }
+                    if alpha >= beta {
+                        return entry.value;
+                    }
+                }
+            }
+        }
+
+        let (alpha_orig, beta_orig) = (alpha, beta);
+        let mut best: i32 = i32::MIN;
+        let mut best_move: BitBoard = 0;
+
+        for mv in self.order_moves(oth, color, moves, hash_move) {
+            let score = -self.negamax(oth.make_move(color, mv), color.invert(), -beta, -alpha);
+            if score > best {
+                best = score;
+                best_move = mv;
+                alpha = std::cmp::max(alpha, score);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        if use_table && !self.control.is_stopped() {
+            let bound = if best <= alpha_orig {
+                Bound::Upper
+            } else if best >= beta_orig {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.table.store(hash, Entry { depth: n_empties, bound, value: best, best_move });
+        }
+
+        best
+    }
+
+    /*
+     * Orders the moves: the hash move comes first, then, with enough empty squares left, the
+     * moves leaving the opponent the fewest replies (fastest-first), ties and smaller positions
+     * being ordered by parity.
+     */
+    fn order_moves(
+        &self,
+        oth: Othello,
+        color: Color,
+        mut moves: BitBoard,
+        hash_move: BitBoard,
+    ) -> Vec<BitBoard> {
+        let empties: BitBoard = oth.empty_squares();
+        let odd = odd_squares(empties);
+
+        let mut scored: Vec<(u8, BitBoard)> = Vec::with_capacity(moves.pop_cnt() as usize);
+
+        if empties.pop_cnt() > FASTEST_FIRST_EMPTIES {
+            while moves != 0 {
+                let mv = moves.pop_lsb();
+                let mobility = oth.make_move(color, mv).gen_moves(color.invert()).pop_cnt();
+                scored.push((2 * mobility + (mv & odd == 0) as u8, mv));
+            }
+        } else {
+            while moves != 0 {
+                let mv = moves.pop_lsb();
+                scored.push(((mv & odd == 0) as u8, mv));
+            }
+        }
+
+        scored.sort_by_key(|&(key, mv)| (mv != hash_move, key));
+        scored.into_iter().map(|(_, mv)| mv).collect()
+    }
+
+    /*
+     * Solves positions with two to four empty squares, trying the empty squares directly instead
+     * of generating moves, those in odd regions first.
+     */
+    fn solve_shallow(
+        &mut self,
+        oth: Othello,
+        color: Color,
+        mut alpha: i32,
+        beta: i32,
+        empties: BitBoard,
+    ) -> i32 {
+        self.nodes += 1;
+
+        let odd = odd_squares(empties);
+        let opp: BitBoard = oth.get_bitboard(color.invert());
+        let mut best: i32 = i32::MIN;
+
+        for mut squares in [empties & odd, empties & !odd] {
+            while squares != 0 {
+                let sq = squares.pop_lsb();
+                let new_oth = oth.make_move(color, sq);
+                if new_oth.get_bitboard(color.invert()) == opp {
+                    continue;
+                }
+                let score = -self.negamax(new_oth, color.invert(), -beta, -alpha);
+                if score > best {
+                    best = score;
+                    alpha = std::cmp::max(alpha, score);
+                    if alpha >= beta {
+                        return best;
+                    }
+                }
+            }
+        }
+
+        if best == i32::MIN {
+            if oth.gen_moves(color.invert()) == 0 {
+                return final_score(oth, color);
+            }
+            return -self.negamax(oth, color.invert(), -beta, -alpha);
+        }
+
+        best
+    }
+
+    /*
+     * Solves positions with a single empty square: whoever can play there does, and the game is
+     * over.
+     */
+    fn solve_last(&mut self, oth: Othello, color: Color, sq: BitBoard) -> i32 {
+        self.nodes += 1;
+
+        for &playing in &[color, color.invert()] {
+            let new_oth = oth.make_move(playing, sq);
+            if new_oth.get_bitboard(playing.invert()) != oth.get_bitboard(playing.invert()) {
+                return final_score(new_oth, color);
+            }
+        }
+
+        final_score(oth, color)
+    }
+}
+
+//#################################################################################################
+//
+//                                    SOLVER TEST
+//
+//#################################################################################################
+
+/*
+ * Checks the solver against a plain negamax on positions reached by pseudo-random games.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * A straightforward negamax to the end of the game, without any pruning.
+     */
+    fn brute_force(pos: Position) -> i32 {
+        if pos.is_game_over() {
+            return final_score(pos.board(), pos.side_to_move());
+        }
+        if pos.must_pass() {
+            return -brute_force(pos.pass());
+        }
+
+        let mut moves = pos.legal_moves();
+        let mut best = i32::MIN;
+        while moves != 0 {
+            best = std::cmp::max(best, -brute_force(pos.play(moves.pop_lsb())));
+        }
+        best
+    }
+
+    /*
+     * Plays pseudo-random moves from the starting position until the given number of empty
+     * squares is left, or the game is over.
+     */
+    fn random_position(seed: u64, empties: u8) -> Position {
+        let mut pos = Position::new();
+        let mut state = seed;
+
+        while pos.empties() > empties && !pos.is_game_over() {
+            if pos.must_pass() {
+                pos = pos.pass();
+            }
+            let mut moves = pos.legal_moves();
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            for _ in 0..(state >> 33) % moves.pop_cnt() as u64 {
+                moves.pop_lsb();
+            }
+            pos = pos.play(moves.pop_lsb());
+        }
+
+        pos
+    }
+
+    #[test]
+    fn exact_scores() {
+        let table = TranspositionTable::new(1);
+        let control = Control::new(None);
+
+        for seed in 0..40 {
+            let pos = random_position(seed, 6 + (seed % 5) as u8);
+            let (score, mv) = EndgameSolver::new(&table, &control).solve(pos);
+
+            assert_eq!(score, brute_force(pos), "Wrong score for seed {}", seed);
+            if mv != 0 {
+                assert_eq!(-brute_force(pos.play(mv)), score, "Wrong move for seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn wld_outcomes() {
+        let table = TranspositionTable::new(1);
+        let control = Control::new(None);
+
+        for seed in 0..40 {
+            let pos = random_position(seed, 6 + (seed % 5) as u8);
+            let (outcome, mv) = EndgameSolver::new(&table, &control).solve_wld(pos);
+
+            let expected = Outcome::from_score(brute_force(pos));
+            assert_eq!(outcome, expected, "Wrong outcome for seed {}", seed);
+            if mv != 0 {
+                let reached = Outcome::from_score(-brute_force(pos.play(mv)));
+                assert_eq!(reached, expected, "Wrong move for seed {}", seed);
+            }
+        }
+    }
+}
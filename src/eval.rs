@@ -0,0 +1,364 @@
+use crate::types::*;
+use crate::othello::*;
+use crate::files::*;
+
+use std::io::{Read, Write};
+use std::sync::{Arc, OnceLock};
+
+//#################################################################################################
+//
+//                                       CONSTANTS
+//
+//#################################################################################################
+
+/*
+ * The number of evaluation units per disc of the pattern evaluator.
+ */
+pub const DISC_SCALE: i32 = 128;
+
+/*
+ * The number of evaluation units per disc of the mask evaluator, which has no such scale built
+ * in: the least-squares fit of the final disc differences of depth 1 self-play games to its
+ * midgame evaluations, derived by the mask_disc_scale test of the training.
+ */
+const MASK_DISC_SCALE: i32 = 7;
+
+/*
+ * The number of empty squares covered by each game phase, each phase having its own weights.
+ */
+pub const EMPTIES_PER_PHASE: u8 = 5;
+
+/*
+ * The number of game phases, from the last one (phase 0, with 0 to 4 empty squares) to the
+ * opening.
+ */
+pub const NUM_PHASES: usize = 60 / EMPTIES_PER_PHASE as usize + 1;
+
+/*
+ * The magic bytes and version at the start of a weight file.
+ */
+const MAGIC: &[u8; 4] = b"OTHW";
+const VERSION: u32 = 1;
+
+/*
+ * The shapes of the patterns, as lists of squares (x + 8 * y, a1 being 0) in one corner or along
+ * one side of the board. Every shape is also applied to its rotations and reflections.
+ */
+const SHAPES: [&[u8]; 11] = [
+    // Edge plus the two X-squares.
+    &[0, 1, 2, 3, 4, 5, 6, 7, 9, 14],
+    // Corner 3x3.
+    &[0, 1, 2, 8, 9, 10, 16, 17, 18],
+    // Corner 2x5.
+    &[0, 1, 2, 3, 4, 8, 9, 10, 11, 12],
+    // Rows 2, 3 and 4.
+    &[8, 9, 10, 11, 12, 13, 14, 15],
+    &[16, 17, 18, 19, 20, 21, 22, 23],
+    &[24, 25, 26, 27, 28, 29, 30, 31],
+    // Diagonals of 8 to 4 squares.
+    &[0, 9, 18, 27, 36, 45, 54, 63],
+    &[1, 10, 19, 28, 37, 46, 55],
+    &[2, 11, 20, 29, 38, 47],
+    &[3, 12, 21, 30, 39],
+    &[4, 13, 22, 31],
+];
+
+//#################################################################################################
+//
+//                                     MASK EVALUATION
+//
+//#################################################################################################
+
+/*
+ * The evaluation function, using masks to do a weighted sum of the board and the move generation
+ * algorithm to measure mobility for both players. The result is given from Black's point of view.
+ */
+#[inline(always)]
+fn evaluate_masks(oth: Othello) -> i32 {
+    let mut res: i32 = 0;
+
+    let black: BitBoard = oth.get_bitboard(Color::Black);
+    let white: BitBoard = oth.get_bitboard(Color::White);
+
+    macro_rules! delta_mask {
+        ($mask: expr, $val: expr) => {
+            let weight: i32 = $val;
+            res += weight * ((black & $mask).pop_cnt() as i32 - (white & $mask).pop_cnt() as i32);
+        }
+    }
+
+    delta_mask!(0x8100000000000081, 100);
+    delta_mask!(0x2400810000810024, 10);
+    delta_mask!(0x1800008181000018, 5);
+    delta_mask!(0x00003C3C3C3C0000, -1);
+    delta_mask!(0x003C424242423C00, -2);
+    delta_mask!(0x4281000000008142, -20);
+    delta_mask!(0x0042000000004200, -50);
+
+    let black_mobility: i32 = oth.gen_moves(Color::Black).pop_cnt() as i32;
+    let white_mobility: i32 = oth.gen_moves(Color::White).pop_cnt() as i32;
+    res += 5 * (black_mobility - white_mobility);
+
+    res
+}
+
+//#################################################################################################
+//
+//                                       PATTERNS
+//
+//#################################################################################################
+
+/*
+ * A pattern: its squares, each instance being one of the distinct rotations or reflections of
+ * the shape, and where its weights start within the weights of a phase.
+ */
+pub struct Pattern {
+    pub instances: Vec<Vec<u8>>,
+    pub size: usize,
+    pub offset: usize,
+}
+
+/*
+ * Returns the patterns, computed once from their shapes. Instances covering the same set of
+ * squares as a previous one are left out.
+ */
+pub fn patterns() -> &'static [Pattern] {
+    static PATTERNS: OnceLock<Vec<Pattern>> = OnceLock::new();
+
+    PATTERNS.get_or_init(|| {
+        let mut offset = 1;
+
+        SHAPES.iter().map(|shape| {
+            let mut instances: Vec<Vec<u8>> = vec![];
+            let mut seen: Vec<BitBoard> = vec![];
+
+            for symmetry in Symmetry::all() {
+                let instance: Vec<u8> =
+                    shape.iter().map(|&sq| symmetry.transform_square(sq)).collect();
+                let set = instance.iter().fold(0, |set, &sq| set | 1u64 << sq);
+                if !seen.contains(&set) {
+                    seen.push(set);
+                    instances.push(instance);
+                }
+            }
+
+            let size = 3usize.pow(shape.len() as u32);
+            let pattern = Pattern { instances, size, offset };
+            offset += size;
+            pattern
+        }).collect()
+    })
+}
+
+/*
+ * Returns the number of weights of a phase: one bias followed by the weights of every pattern.
+ */
+pub fn phase_size() -> usize {
+    let last = patterns().last().unwrap();
+    last.offset + last.size
+}
+
+/*
+ * Returns the phase of a position with the given number of empty squares.
+ */
+#[inline(always)]
+pub fn phase(empties: u8) -> usize {
+    std::cmp::min(empties / EMPTIES_PER_PHASE, NUM_PHASES as u8 - 1) as usize
+}
+
+/*
+ * Returns the index of the configuration of the given squares, each square being a ternary digit
+ * worth 0 when empty, 1 when black and 2 when white.
+ */
+#[inline(always)]
+pub fn pattern_index(black: BitBoard, white: BitBoard, squares: &[u8]) -> usize {
+    squares.iter().fold(0, |index, &sq| {
+        3 * index + ((black >> sq) & 1) as usize + 2 * ((white >> sq) & 1) as usize
+    })
+}
+
+/*
+ * Calls the given function with the index, within the weights of a phase, of every feature of the
+ * position: the bias, then the configuration of every instance of every pattern.
+ */
+#[inline(always)]
+pub fn for_each_feature(oth: Othello, mut f: impl FnMut(usize)) {
+    let black = oth.get_bitboard(Color::Black);
+    let white = oth.get_bitboard(Color::White);
+
+    f(0);
+    for pattern in patterns() {
+        for instance in &pattern.instances {
+            f(pattern.offset + pattern_index(black, white, instance));
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                  PATTERN WEIGHTS TYPE
+//
+//#################################################################################################
+
+/*
+ * The weights of the pattern evaluator for every phase, in units of 1 / DISC_SCALE disc from
+ * Black's point of view.
+ */
+pub struct PatternWeights {
+    weights: Vec<i16>,
+}
+
+impl PatternWeights {
+    /*
+     * Creates weights from the given values, which must hold NUM_PHASES * phase_size() weights.
+     */
+    pub fn new(weights: Vec<i16>) -> PatternWeights {
+        assert_eq!(weights.len(), NUM_PHASES * phase_size(), "Wrong number of pattern weights");
+        PatternWeights { weights }
+    }
+
+    /*
+     * Reads weights from a weight file: the magic bytes "OTHW", the version and the number of
+     * phases as little-endian u32, then the weights of each phase as little-endian i16.
+     */
+    pub fn read(mut reader: impl Read) -> std::io::Result<PatternWeights> {
+        if read_header(&mut reader, MAGIC, VERSION, "weight")? as usize != NUM_PHASES {
+            return Err(invalid_data("wrong number of phases in weight file"));
+        }
+
+        let mut bytes: Vec<u8> = vec![];
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() != 2 * NUM_PHASES * phase_size() {
+            return Err(invalid_data("wrong number of weights in weight file"));
+        }
+
+        let weights = bytes.chunks(2).map(|w| i16::from_le_bytes([w[0], w[1]])).collect();
+        Ok(PatternWeights { weights })
+    }
+
+    /*
+     * Loads weights from the weight file at the given path.
+     */
+    pub fn load(path: &str) -> std::io::Result<PatternWeights> {
+        PatternWeights::read(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /*
+     * Writes the weights in the format read by PatternWeights::read.
+     */
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        write_header(&mut writer, MAGIC, VERSION, NUM_PHASES as u32)?;
+        for weight in &self.weights {
+            writer.write_all(&weight.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /*
+     * Saves the weights to a weight file at the given path.
+     */
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        self.write(std::io::BufWriter::new(std::fs::File::create(path)?))
+    }
+
+    /*
+     * Returns the sum of the weights of the features of the position, from Black's point of view.
+     */
+    #[inline(always)]
+    fn evaluate(&self, oth: Othello) -> i32 {
+        let weights = &self.weights[phase(oth.empty_squares().pop_cnt()) * phase_size()..];
+        let mut res: i32 = 0;
+        for_each_feature(oth, |feature| res += weights[feature] as i32);
+        res
+    }
+}
+
+//#################################################################################################
+//
+//                                    EVALUATOR TYPE
+//
+//#################################################################################################
+
+/*
+ * The evaluation function used by the search: either the pattern evaluator with weights loaded at
+ * runtime, or the mask evaluator as a fallback when there are none.
+ */
+#[derive(Clone)]
+pub enum Evaluator {
+    Masks,
+    Patterns(Arc<PatternWeights>),
+}
+
+impl Evaluator {
+    /*
+     * Returns the number of evaluation units per disc.
+     */
+    pub fn disc_scale(&self) -> i32 {
+        match self {
+            Evaluator::Masks => MASK_DISC_SCALE,
+            Evaluator::Patterns(_) => DISC_SCALE,
+        }
+    }
+
+    /*
+     * Evaluates the position from the point of view of the given color.
+     */
+    #[inline(always)]
+    pub fn evaluate(&self, oth: Othello, color: Color) -> i32 {
+        let res = match self {
+            Evaluator::Masks => evaluate_masks(oth),
+            Evaluator::Patterns(weights) => weights.evaluate(oth),
+        };
+
+        match color {
+            Color::Black => res,
+            Color::White => -res,
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                      EVAL TESTS
+//
+//#################################################################################################
+
+/*
+ * Checks the pattern instances and the reading of weight files.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_instances() {
+        let counts: Vec<usize> =
+            patterns().iter().map(|pattern| pattern.instances.len()).collect();
+        assert_eq!(counts, [4, 4, 8, 4, 4, 4, 2, 4, 4, 4, 4]);
+    }
+
+    #[test]
+    fn read_weights() {
+        let weights: Vec<i16> = (0..NUM_PHASES * phase_size())
+            .map(|i| (i % 1000) as i16 - 500)
+            .collect();
+        let mut file: Vec<u8> = vec![];
+        PatternWeights::new(weights.clone()).write(&mut file).unwrap();
+        assert_eq!(PatternWeights::read(file.as_slice()).unwrap().weights, weights);
+
+        assert!(PatternWeights::read(&file[..file.len() - 1]).is_err());
+        file[0] = b'X';
+        assert!(PatternWeights::read(file.as_slice()).is_err());
+    }
+
+    #[test]
+    fn bias_only() {
+        let weights: Vec<i16> = (0..NUM_PHASES * phase_size())
+            .map(|i| if i % phase_size() == 0 { (i / phase_size()) as i16 } else { 0 })
+            .collect();
+        let evaluator = Evaluator::Patterns(Arc::new(PatternWeights::new(weights)));
+
+        assert_eq!(evaluator.evaluate(Othello::new(), Color::Black), phase(60) as i32);
+        assert_eq!(evaluator.evaluate(Othello::new(), Color::White), -(phase(60) as i32));
+    }
+}
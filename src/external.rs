@@ -0,0 +1,305 @@
+use crate::types::*;
+use crate::position::*;
+use crate::game::*;
+use crate::ggf::*;
+
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+//#################################################################################################
+//
+//                                       CONSTANTS
+//
+//#################################################################################################
+
+/*
+ * How long an engine has to exit once asked to quit, before being stopped.
+ */
+const QUIT_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+//#################################################################################################
+//
+//                                  EXTERNAL ENGINE PLAYER
+//
+//#################################################################################################
+
+/*
+ * A player forwarding the game to another engine, such as Edax or another build of this program,
+ * run as a child process speaking the NBoard protocol. The lines the engine writes are read by a
+ * separate thread, so that waiting for them can time out. An engine that crashes, runs out of
+ * time or answers with an illegal move is stopped and resigns, then and on every later turn.
+ */
+pub struct ExternalEnginePlayer {
+    name: String,
+    child: RefCell<Child>,
+    stdin: RefCell<ChildStdin>,
+    lines: Receiver<String>,
+    timeout: Duration,
+    failure: RefCell<Option<String>>,
+}
+
+impl ExternalEnginePlayer {
+    /*
+     * Launches the engine with the given command line, split on whitespace, sets its depth if
+     * given and checks that it answers within the given time, which it then has for every move.
+     */
+    pub fn new(
+        command: &str,
+        depth: Option<u8>,
+        timeout: Duration,
+    ) -> std::io::Result<ExternalEnginePlayer> {
+        let failed = |err: &dyn std::fmt::Display| {
+            format!("engine {} failed to start: {}", command, err)
+        };
+
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty engine command")
+        })?;
+
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| std::io::Error::new(err.kind(), failed(&err)))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, lines) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let engine = ExternalEnginePlayer {
+            name: command.to_owned(),
+            child: RefCell::new(child),
+            stdin: RefCell::new(stdin),
+            lines,
+            timeout,
+            failure: RefCell::new(None),
+        };
+
+        let handshake = engine.send("nboard 2")
+            .and_then(|_| match depth {
+                Some(depth) => engine.send(&format!("set depth {}", depth)),
+                None => Ok(()),
+            })
+            .and_then(|_| engine.send("ping 1"))
+            .and_then(|_| engine.wait_for(|line| line == "pong 1").map(|_| ()));
+        handshake.map_err(|err| std::io::Error::other(failed(&err)))?;
+
+        Ok(engine)
+    }
+
+    /*
+     * Sends a command to the engine.
+     */
+    fn send(&self, command: &str) -> Result<(), String> {
+        let mut stdin = self.stdin.borrow_mut();
+        writeln!(stdin, "{}", command)
+            .and_then(|_| stdin.flush())
+            .map_err(|_| "the engine exited".to_owned())
+    }
+
+    /*
+     * Reads the lines of the engine until one is accepted by the predicate, which is returned,
+     * failing if the engine exits or takes longer than its timeout.
+     */
+    fn wait_for(&self, accept: impl Fn(&str) -> bool) -> Result<String, String> {
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            match self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) if accept(line.trim()) => return Ok(line.trim().to_owned()),
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("no answer within {:.1}s", self.timeout.as_secs_f64()));
+                },
+                Err(RecvTimeoutError::Disconnected) => return Err("the engine exited".to_owned()),
+            }
+        }
+    }
+
+    /*
+     * Asks the engine for its move in the current position of the game, sending the whole game,
+     * and checks that the move is legal. The score of the result is the evaluation given by the
+     * engine, rounded to discs.
+     */
+    fn request(&self, game: &Game) -> Result<SearchResult, String> {
+        if let Some(failure) = self.failure.borrow().as_ref() {
+            return Err(failure.clone());
+        }
+
+        let start = Instant::now();
+        let pos: Position = game.position();
+
+        let answer = self.send(&format!("set game {}", GgfGame::from_game(game)))
+            .and_then(|_| self.send("go"))
+            .and_then(|_| self.wait_for(|line| line.starts_with("===")));
+
+        let result = answer.and_then(|answer| {
+            let mut fields = answer.trim_start_matches('=').trim().split('/');
+            let mv: Move = fields.next().unwrap_or("").trim().parse()
+                .map_err(|err: ParseMoveError| err.to_string())?;
+            if BitBoard::from(mv) & pos.legal_moves() == 0 {
+                return Err(format!("illegal move {}", mv));
+            }
+
+            let mut result = SearchResult::new(mv.into());
+            result.score = fields.next()
+                .and_then(|eval| eval.trim().parse::<f32>().ok())
+                .map_or(0, |eval| eval.round() as i32);
+            result.elapsed = start.elapsed();
+            Ok(result)
+        });
+
+        result.map_err(|err| {
+            let failure = format!("engine {}: {}", self.name, err);
+            let _ = self.child.borrow_mut().kill();
+            *self.failure.borrow_mut() = Some(failure.clone());
+            failure
+        })
+    }
+}
+
+impl Player for ExternalEnginePlayer {
+    /*
+     * Gets the move of the engine, as ExternalEnginePlayer::think does.
+     */
+    fn chose_move(&self, pos: Position) -> BitBoard {
+        self.think(pos).best_move
+    }
+
+    /*
+     * Gets the move of the engine, along with its evaluation. As a move has to be given, an
+     * engine failing to give a legal one is reported and replaced by the first legal move, or a
+     * pass if there is none: players able to resign should be asked with
+     * ExternalEnginePlayer::act.
+     */
+    fn think(&self, pos: Position) -> SearchResult {
+        self.request(&Game::create(pos)).unwrap_or_else(|failure| {
+            eprintln!("{}, playing the first legal move instead", failure);
+            let moves = pos.legal_moves();
+            SearchResult::new(moves & moves.wrapping_neg())
+        })
+    }
+
+    /*
+     * Gets the move of the engine for the game, resigning if it fails to give a legal one.
+     */
+    fn act(&self, game: &Game) -> Action {
+        match self.request(game) {
+            Ok(result) => Action::Play(result),
+            Err(failure) => {
+                eprintln!("{}", failure);
+                Action::Resign
+            },
+        }
+    }
+}
+
+impl Drop for ExternalEnginePlayer {
+    /*
+     * Asks the engine to quit, and stops it if it doesn't shortly.
+     */
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_GRACE_PERIOD;
+        let mut child = self.child.borrow_mut();
+
+        while Instant::now() < deadline {
+            if !matches!(child.try_wait(), Ok(None)) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+//#################################################################################################
+//
+//                                  EXTERNAL ENGINE TESTS
+//
+//#################################################################################################
+
+/*
+ * Checks legal moves, illegal moves, timeouts and crashes against small shell scripts posing as
+ * engines.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /*
+     * Launches a script answering pings, and "go" with the given line, and ignoring "quit" if
+     * told to. The script is removed once the shell has read it, which is done by the time it
+     * answers the first ping.
+     */
+    fn fake_engine(
+        answer: &str,
+        ignore_quit: bool,
+        timeout: Duration,
+    ) -> std::io::Result<ExternalEnginePlayer> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let quit = if ignore_quit { "" } else { "quit) exit;;" };
+        let script = format!(
+            "while read c a; do case $c in ping) echo pong $a;; go) {};; {} esac; done",
+            answer, quit,
+        );
+        let id = COUNT.fetch_add(1, Ordering::Relaxed);
+        let name = format!("fake-engine-{}-{}.sh", std::process::id(), id);
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, script)?;
+
+        let command = format!("sh {}", path.display());
+        let engine = ExternalEnginePlayer::new(&command, Some(4), timeout);
+        std::fs::remove_file(&path)?;
+        engine
+    }
+
+    #[test]
+    fn engine_moves() {
+        let timeout = Duration::from_millis(500);
+
+        let engine = fake_engine("echo === F5/1.6/0.2", false, timeout).unwrap();
+        let result = engine.think(Position::new());
+        assert_eq!((result.best_move, result.score), (1 << 37, 2));
+
+        // f5 is taken once played.
+        let game = Game::from_transcript("f5").unwrap();
+        assert!(matches!(engine.act(&game), Action::Resign));
+        assert!(matches!(engine.act(&Game::new()), Action::Resign));
+        // Without resigning, the first legal move is played instead.
+        let moves = game.position().legal_moves();
+        assert_eq!(engine.think(game.position()).best_move, moves & moves.wrapping_neg());
+
+        let silent = fake_engine("true", false, timeout).unwrap();
+        assert!(matches!(silent.act(&Game::new()), Action::Resign));
+
+        let crashing = fake_engine("exit 1", false, timeout).unwrap();
+        assert!(matches!(crashing.act(&Game::new()), Action::Resign));
+
+        // An engine ignoring "quit" is stopped shortly, whatever its timeout.
+        let stubborn = fake_engine("true", true, Duration::from_secs(60)).unwrap();
+        let start = Instant::now();
+        drop(stubborn);
+        assert!(start.elapsed() < 4 * QUIT_GRACE_PERIOD);
+
+        assert!(ExternalEnginePlayer::new("sh -c true", None, timeout).is_err());
+        assert!(ExternalEnginePlayer::new("/nonexistent/engine", None, timeout).is_err());
+    }
+}
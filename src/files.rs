@@ -0,0 +1,80 @@
+use std::io::{Read, Write};
+
+//#################################################################################################
+//
+//                                      FILE HELPERS
+//
+//#################################################################################################
+
+/*
+ * Returns the error reporting that a file holds invalid data, with the given description.
+ */
+pub fn invalid_data(msg: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/*
+ * Writes the header of a binary file: its magic bytes, then its version and the number of records
+ * it holds as little-endian u32.
+ */
+pub fn write_header(
+    writer: &mut impl Write,
+    magic: &[u8; 4],
+    version: u32,
+    count: u32,
+) -> std::io::Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&version.to_le_bytes())?;
+    writer.write_all(&count.to_le_bytes())
+}
+
+/*
+ * Reads the header written by write_header, checking its magic bytes and version, and returns
+ * the number of records. The kind of file is named in the errors, as in "not a book file".
+ */
+pub fn read_header(
+    reader: &mut impl Read,
+    magic: &[u8; 4],
+    version: u32,
+    kind: &str,
+) -> std::io::Result<u32> {
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    let word = |i: usize| {
+        u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]])
+    };
+
+    if &header[0..4] != magic {
+        return Err(invalid_data(format!("not a {} file", kind)));
+    }
+    if word(4) != version {
+        return Err(invalid_data(format!("unsupported {} file version", kind)));
+    }
+    Ok(word(8))
+}
+
+//#################################################################################################
+//
+//                                   FILE HELPERS TEST
+//
+//#################################################################################################
+
+/*
+ * Checks that a header is read back, and that other files are rejected.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let mut file: Vec<u8> = vec![];
+        write_header(&mut file, b"TEST", 3, 42).unwrap();
+
+        assert_eq!(read_header(&mut file.as_slice(), b"TEST", 3, "test").unwrap(), 42);
+        let err = read_header(&mut file.as_slice(), b"OTHB", 3, "book").unwrap_err();
+        assert_eq!(err.to_string(), "not a book file");
+        assert!(read_header(&mut file.as_slice(), b"TEST", 4, "test").is_err());
+        assert!(read_header(&mut &file[..8], b"TEST", 3, "test").is_err());
+    }
+}
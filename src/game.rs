@@ -0,0 +1,275 @@
+use crate::types::*;
+use crate::position::*;
+
+//#################################################################################################
+//
+//                                       GAME TYPE
+//
+//#################################################################################################
+
+/*
+ * A game with its full history: every position from the first one, usually the starting position,
+ * and the moves leading from one to the next, passes being recorded as 0. The game can be stepped
+ * back and forth through its history, and playing a move other than the next one recorded starts
+ * a new branch, dropping the moves that followed.
+ */
+pub struct Game {
+    positions: Vec<Position>,
+    moves: Vec<BitBoard>,
+    ply: usize,
+}
+
+impl Game {
+    /*
+     * Creates a new Game from the starting position.
+     */
+    pub fn new() -> Game {
+        Game::create(Position::new())
+    }
+
+    /*
+     * Creates a new Game from the given position.
+     */
+    pub fn create(start: Position) -> Game {
+        Game { positions: vec![start], moves: vec![], ply: 0 }
+    }
+
+    /*
+     * Returns the current position.
+     */
+    pub fn position(&self) -> Position {
+        self.positions[self.ply]
+    }
+
+    /*
+     * Returns the number of plies played to reach the current position, passes included.
+     */
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /*
+     * Returns the number of plies recorded, which can be more than the current ply after an undo.
+     */
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    /*
+     * Returns the positions from the start of the game to the current one.
+     */
+    pub fn positions(&self) -> &[Position] {
+        &self.positions[..=self.ply]
+    }
+
+    /*
+     * Returns the moves leading to the current position, passes being 0.
+     */
+    pub fn moves(&self) -> &[BitBoard] {
+        &self.moves[..self.ply]
+    }
+
+    /*
+     * Plays a move, or a pass if the move is 0, in the current position. Keeps the recorded moves
+     * if it is the next one, and starts a new branch from the current position otherwise. Returns
+     * false, leaving the game unchanged, if the move is illegal.
+     */
+    pub fn play(&mut self, mv: BitBoard) -> bool {
+        let pos = self.position();
+
+        let next = if mv == 0 && pos.must_pass() {
+            pos.pass()
+        } else if mv != 0 && mv & (mv - 1) == 0 && pos.legal_moves() & mv != 0 {
+            pos.play(mv)
+        } else {
+            return false;
+        };
+
+        if self.moves.get(self.ply) != Some(&mv) {
+            self.moves.truncate(self.ply);
+            self.positions.truncate(self.ply + 1);
+            self.moves.push(mv);
+            self.positions.push(next);
+        }
+        self.ply += 1;
+        true
+    }
+
+    /*
+     * Goes back one ply. Returns false if the game is at its start.
+     */
+    pub fn undo(&mut self) -> bool {
+        self.jump(self.ply.wrapping_sub(1))
+    }
+
+    /*
+     * Goes forward one ply along the recorded moves. Returns false if there is none.
+     */
+    pub fn redo(&mut self) -> bool {
+        self.jump(self.ply + 1)
+    }
+
+    /*
+     * Goes to the position after the given number of plies of the recorded moves. Returns false
+     * if there are not that many.
+     */
+    pub fn jump(&mut self, ply: usize) -> bool {
+        if ply > self.len() {
+            return false;
+        }
+        self.ply = ply;
+        true
+    }
+
+    /*
+     * Replays a transcript, the concatenated list of the moves of a game such as "f5d6c3", in
+     * either case and possibly spaced out. Passes are played implicitly, but may also be written
+     * as "--" or "PA". The game is left at its last position.
+     */
+    pub fn from_transcript(transcript: &str) -> Result<Game, TranscriptError> {
+        let chars: Vec<char> = transcript.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut game: Game = Game::new();
+
+        for (i, chunk) in chars.chunks(2).enumerate() {
+            let ply = i + 1;
+            let mv: Move = chunk.iter().collect::<String>().parse()
+                .map_err(|err| TranscriptError::Invalid(ply, err))?;
+
+            // An explicit pass has already been played implicitly.
+            if mv == Move::Pass && game.moves().last() == Some(&0) {
+                continue;
+            }
+            if !game.play(mv.into()) {
+                return Err(TranscriptError::Illegal(ply, mv));
+            }
+            if game.position().must_pass() {
+                game.play(0);
+            }
+        }
+
+        Ok(game)
+    }
+
+    /*
+     * Returns the transcript of the game up to the current position, passes being left out, as
+     * in "f5d6c3".
+     */
+    pub fn transcript(&self) -> String {
+        self.moves().iter().filter(|&&mv| mv != 0).map(|&mv| Move::from(mv).to_string()).collect()
+    }
+}
+
+/*
+ * Parses the position reached after a transcript, as in "f5d6c3", or a position in the text
+ * format, as in "---...---OX------XO---...--- X", told apart from transcripts by its 65
+ * characters once whitespace and a final ';' are left out, as moves are written with two.
+ */
+pub fn parse_start(text: &str) -> Result<Position, String> {
+    let trimmed = text.trim();
+    let trimmed = trimmed.strip_suffix(';').unwrap_or(trimmed);
+    if trimmed.chars().filter(|c| !c.is_whitespace()).count() == 65 {
+        text.parse::<Position>().map_err(|err| err.to_string())
+    } else {
+        Game::from_transcript(text)
+            .map(|game| game.position())
+            .map_err(|err| format!("invalid moves: {}", err))
+    }
+}
+
+/*
+ * The error returned when replaying an invalid transcript, along with the ply, counted from 1
+ * along the transcript, of the first wrong move.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub enum TranscriptError {
+    Invalid(usize, ParseMoveError),
+    Illegal(usize, Move),
+}
+
+impl std::fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TranscriptError::Invalid(ply, err) => write!(f, "ply {}: {}", ply, err),
+            TranscriptError::Illegal(ply, mv) => write!(f, "ply {}: illegal move {}", ply, mv),
+        }
+    }
+}
+
+impl std::error::Error for TranscriptError {}
+
+//#################################################################################################
+//
+//                                       GAME TEST
+//
+//#################################################################################################
+
+/*
+ * Checks that going back and forth through the history and branching keep the positions right,
+ * and the import and export of transcripts.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history() {
+        let (f5, d6, c3, f6): (BitBoard, BitBoard, BitBoard, BitBoard) =
+            (1 << 37, 1 << 43, 1 << 18, 1 << 45);
+        let mut game = Game::new();
+
+        assert!(!game.undo() && !game.redo());
+        assert!(game.play(f5) && game.play(d6) && game.play(c3));
+        assert!(!game.play(c3) && !game.play(0));
+        assert_eq!(game.position(), Position::new().play(f5).play(d6).play(c3));
+
+        assert!(game.undo() && game.undo());
+        assert_eq!(game.position(), Position::new().play(f5));
+        assert_eq!(game.moves(), [f5]);
+        assert!(game.redo());
+        assert_eq!(game.position(), Position::new().play(f5).play(d6));
+
+        // Playing the recorded move keeps the rest of the game.
+        assert!(game.jump(1) && game.play(d6));
+        assert_eq!(game.len(), 3);
+
+        // Playing another one branches off.
+        assert!(game.jump(1) && game.play(f6));
+        assert_eq!(game.len(), 2);
+        assert!(!game.redo() && !game.jump(3));
+        assert!(game.jump(0));
+        assert_eq!(game.position(), Position::new());
+    }
+
+    #[test]
+    fn transcripts() {
+        let game = Game::from_transcript("f5D6 c3").unwrap();
+        assert_eq!(game.len(), 3);
+        assert_eq!(game.position(), Position::new().play(1 << 37).play(1 << 43).play(1 << 18));
+        assert_eq!(game.positions().len(), 4);
+        assert_eq!(game.transcript(), "f5d6c3");
+
+        let illegal = |ply, mv| Some(TranscriptError::Illegal(ply, mv));
+        assert_eq!(Game::from_transcript("f5f5").err(), illegal(2, Move::Square(37)));
+        assert!(matches!(Game::from_transcript("f5d6z9"), Err(TranscriptError::Invalid(3, _))));
+        assert!(matches!(Game::from_transcript("f5d"), Err(TranscriptError::Invalid(2, _))));
+        assert_eq!(Game::from_transcript("f5--").err(), illegal(2, Move::Pass));
+
+        // A game ending early, one color being wiped out, survives a round trip.
+        let game = Game::from_transcript("f5f4c3c6c5d6f3c4c7").unwrap();
+        assert!(game.position().is_game_over());
+        assert_eq!(Game::from_transcript(&game.transcript()).unwrap().position(), game.position());
+    }
+
+    #[test]
+    fn start_positions() {
+        let after_f5 = Position::new().play(1 << 37);
+        assert_eq!(parse_start("f5"), Ok(after_f5));
+        assert_eq!(parse_start(""), Ok(Position::new()));
+        assert_eq!(parse_start(&after_f5.to_string()), Ok(after_f5));
+        let stars = "---------------------------0*------***-------------------------- 0;";
+        assert_eq!(parse_start(stars), Ok(after_f5));
+
+        assert!(parse_start("f5f5").unwrap_err().starts_with("invalid moves"));
+        assert!(parse_start(&stars.replace('*', "#")).is_err());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::types::*;
+use crate::othello::*;
+use crate::position::*;
+use crate::game::*;
+
+//#################################################################################################
+//
+//                                     GGF MOVE TYPE
+//
+//#################################################################################################
+
+/*
+ * A move of a GGF game: the color playing it, the move itself and, when known, its evaluation and
+ * the time taken to play it in seconds.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GgfMove {
+    pub color: Color,
+    pub mv: Move,
+    pub eval: Option<f32>,
+    pub time: Option<f32>,
+}
+
+//#################################################################################################
+//
+//                                     GGF GAME TYPE
+//
+//#################################################################################################
+
+/*
+ * A game in the Generic Game Format of GGS, such as
+ * "(;GM[Othello]PB[alice]PW[bob]RE[+4.000]BO[8 ... *]B[f5//1.2]W[d6/-2/0.8];)": where and when
+ * it was played, the players and their ratings, the time control, the result as Black's disc
+ * difference, the first position and the moves. Other tags are ignored when reading.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct GgfGame {
+    pub place: String,
+    pub date: String,
+    pub black: String,
+    pub white: String,
+    pub black_rating: Option<f32>,
+    pub white_rating: Option<f32>,
+    pub time_control: String,
+    pub result: Option<f32>,
+    pub start: Position,
+    pub moves: Vec<GgfMove>,
+}
+
+impl GgfGame {
+    /*
+     * Creates a new GgfGame from the given position, with no move and nothing else known.
+     */
+    pub fn new(start: Position) -> GgfGame {
+        GgfGame {
+            place: String::new(),
+            date: String::new(),
+            black: String::new(),
+            white: String::new(),
+            black_rating: None,
+            white_rating: None,
+            time_control: String::new(),
+            result: None,
+            start,
+            moves: vec![],
+        }
+    }
+
+    /*
+     * Creates a GgfGame from the moves of a game up to its current position, passes included,
+     * along with its result if it is over.
+     */
+    pub fn from_game(game: &Game) -> GgfGame {
+        let positions = game.positions();
+        let mut ggf = GgfGame::new(positions[0]);
+
+        ggf.moves = game.moves().iter().zip(positions).map(|(&mv, pos)| {
+            GgfMove { color: pos.side_to_move(), mv: mv.into(), eval: None, time: None }
+        }).collect();
+        ggf.result = game.position().result().map(|score| {
+            score.get(Color::Black) as f32 - score.get(Color::White) as f32
+        });
+
+        ggf
+    }
+
+    /*
+     * Returns the game replayed from its first position, its moves being legal as checked when
+     * it was read.
+     */
+    pub fn to_game(&self) -> Game {
+        let mut game: Game = Game::create(self.start);
+        for mv in &self.moves {
+            game.play(mv.mv.into());
+        }
+        game
+    }
+}
+
+//#################################################################################################
+//
+//                                        READING
+//
+//#################################################################################################
+
+/*
+ * The error returned when reading an invalid GGF game, describing what is wrong.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseGgfError(String);
+
+impl std::fmt::Display for ParseGgfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid GGF game: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseGgfError {}
+
+/*
+ * Reads the value of a tag, after its opening bracket, up to the closing bracket, a backslash
+ * escaping the next character. Returns the value along with the number of bytes read, the closing
+ * bracket included.
+ */
+fn read_value(s: &str) -> Result<(String, usize), ParseGgfError> {
+    let mut value: String = String::new();
+    let mut chars = s.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            ']' => return Ok((value, i + 1)),
+            '\\' => value.extend(chars.next().map(|(_, c)| c)),
+            c => value.push(c),
+        }
+    }
+
+    Err(ParseGgfError("unclosed tag value".to_owned()))
+}
+
+/*
+ * Parses a number of a tag, an empty value meaning it is unknown.
+ */
+fn parse_number(tag: &str, value: &str) -> Result<Option<f32>, ParseGgfError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value.parse()
+        .map(Some)
+        .map_err(|_| ParseGgfError(format!("invalid number \"{}\" in {}", value, tag)))
+}
+
+/*
+ * Parses a board, as in "8 -------- ... ---O*--- ---*O--- ... -------- *": its size, which must
+ * be 8, then its squares row by row from a1 to h8, '*' being black, 'O' white and '-' empty, and
+ * the color to move.
+ */
+fn parse_board(value: &str) -> Result<Position, ParseGgfError> {
+    let value = value.trim();
+    let (size, squares) = value.split_at(value.find(char::is_whitespace).unwrap_or(value.len()));
+    if size != "8" {
+        return Err(ParseGgfError(format!("unsupported board size \"{}\"", size)));
+    }
+
+    let squares: Vec<char> = squares.chars().filter(|c| !c.is_whitespace()).collect();
+    if squares.len() != 65 {
+        let count = squares.len().saturating_sub(1);
+        return Err(ParseGgfError(format!("{} squares on the board instead of 64", count)));
+    }
+
+    let (mut black, mut white): (BitBoard, BitBoard) = (0, 0);
+    for (sq, &c) in squares[..64].iter().enumerate() {
+        match c {
+            '*' => black |= 1 << sq,
+            'O' => white |= 1 << sq,
+            '-' => (),
+            c => return Err(ParseGgfError(format!("invalid square '{}' on the board", c))),
+        }
+    }
+
+    let to_move = match squares[64] {
+        '*' => Color::Black,
+        'O' => Color::White,
+        c => return Err(ParseGgfError(format!("invalid color to move '{}'", c))),
+    };
+
+    Ok(Position::create(Othello::create(black, white), to_move))
+}
+
+/*
+ * Parses a move, as in "f5", "f5/-2.5" or "f5/-2.5/0.8": the move itself, its evaluation and the
+ * time taken to play it, the last two being optional.
+ */
+fn parse_move(color: Color, value: &str) -> Result<GgfMove, ParseGgfError> {
+    let mut fields = value.split('/');
+    let mv: Move = fields.next().unwrap_or("").parse()
+        .map_err(|err: ParseMoveError| ParseGgfError(err.to_string()))?;
+    let eval = parse_number("move evaluation", fields.next().unwrap_or("").trim())?;
+    let time = parse_number("move time", fields.next().unwrap_or("").trim())?;
+    Ok(GgfMove { color, mv, eval, time })
+}
+
+impl std::str::FromStr for GgfGame {
+    type Err = ParseGgfError;
+
+    /*
+     * Parses a game, as written by GGS, enclosed in "(;" and ";)". The moves are checked to be
+     * legal, and passes left out by the game are added.
+     */
+    fn from_str(s: &str) -> Result<GgfGame, ParseGgfError> {
+        let mut rest = s.trim()
+            .strip_prefix("(;")
+            .and_then(|rest| rest.strip_suffix(";)"))
+            .ok_or_else(|| ParseGgfError("not enclosed in \"(;\" and \";)\"".to_owned()))?;
+
+        let mut ggf = GgfGame::new(Position::new());
+        let mut moves: Vec<GgfMove> = vec![];
+
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+
+            let open = rest.find('[')
+                .ok_or_else(|| ParseGgfError(format!("no value for \"{}\"", rest)))?;
+            let tag = rest[..open].trim();
+            let (value, len) = read_value(&rest[open + 1..])?;
+            rest = &rest[open + 1 + len..];
+
+            match tag {
+                "GM" if !value.eq_ignore_ascii_case("othello") => {
+                    return Err(ParseGgfError(format!("not an Othello game but \"{}\"", value)));
+                },
+                "PC" => ggf.place = value,
+                "DT" => ggf.date = value,
+                "PB" => ggf.black = value,
+                "PW" => ggf.white = value,
+                "RB" => ggf.black_rating = parse_number(tag, &value)?,
+                "RW" => ggf.white_rating = parse_number(tag, &value)?,
+                "TI" => ggf.time_control = value,
+                // The result may be followed by how the game ended, as in "+64.000:r", and is
+                // unknown when it is not a number, as the "?" of unfinished games.
+                "RE" => ggf.result = value.split(':').next().unwrap_or("").trim().parse().ok(),
+                "BO" => ggf.start = parse_board(&value)?,
+                "B" => moves.push(parse_move(Color::Black, &value)?),
+                "W" => moves.push(parse_move(Color::White, &value)?),
+                _ => (),
+            }
+        }
+
+        let mut pos: Position = ggf.start;
+        for (i, ggf_move) in moves.into_iter().enumerate() {
+            if ggf_move.color != pos.side_to_move() && pos.must_pass() {
+                let color = pos.side_to_move();
+                ggf.moves.push(GgfMove { color, mv: Move::Pass, eval: None, time: None });
+                pos = pos.pass();
+            }
+
+            let mv: BitBoard = ggf_move.mv.into();
+            let playable = if mv == 0 { pos.must_pass() } else { pos.legal_moves() & mv != 0 };
+            if ggf_move.color != pos.side_to_move() || !playable {
+                return Err(ParseGgfError(format!(
+                    "move {}: illegal move {} for {:?}",
+                    i + 1, ggf_move.mv, ggf_move.color,
+                )));
+            }
+
+            pos = if mv == 0 { pos.pass() } else { pos.play(mv) };
+            ggf.moves.push(ggf_move);
+        }
+
+        Ok(ggf)
+    }
+}
+
+//#################################################################################################
+//
+//                                        WRITING
+//
+//#################################################################################################
+
+/*
+ * Escapes the backslashes and closing brackets of a tag value.
+ */
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(']', "\\]")
+}
+
+impl std::fmt::Display for GgfGame {
+    /*
+     * Formats the game on a single line, in the format read by GgfGame::from_str, leaving out the
+     * tags whose value is unknown.
+     */
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "(;GM[Othello]")?;
+
+        let tags = [
+            ("PC", &self.place),
+            ("DT", &self.date),
+            ("PB", &self.black),
+            ("PW", &self.white),
+        ];
+        for (tag, value) in tags {
+            if !value.is_empty() {
+                write!(f, "{}[{}]", tag, escape(value))?;
+            }
+        }
+        for (tag, rating) in [("RB", self.black_rating), ("RW", self.white_rating)] {
+            if let Some(rating) = rating {
+                write!(f, "{}[{}]", tag, rating)?;
+            }
+        }
+        if !self.time_control.is_empty() {
+            write!(f, "TI[{}]", escape(&self.time_control))?;
+        }
+        write!(f, "TY[8]")?;
+        if let Some(result) = self.result {
+            write!(f, "RE[{:+.3}]", result)?;
+        }
+
+        write!(f, "BO[8")?;
+        let board: Othello = self.start.board();
+        for y in 0..8 {
+            write!(f, " ")?;
+            for x in 0..8 {
+                match board.get_square(x, y) {
+                    Square::Black => write!(f, "*")?,
+                    Square::White => write!(f, "O")?,
+                    Square::Empty => write!(f, "-")?,
+                }
+            }
+        }
+        write!(f, " {}]", if self.start.side_to_move() == Color::Black { '*' } else { 'O' })?;
+
+        for mv in &self.moves {
+            let color = if mv.color == Color::Black { "B" } else { "W" };
+            match mv.mv {
+                Move::Pass => write!(f, "{}[PA", color)?,
+                square => write!(f, "{}[{}", color, square)?,
+            }
+            match (mv.eval, mv.time) {
+                (None, None) => (),
+                (eval, time) => {
+                    write!(f, "/{}", eval.map_or(String::new(), |eval| eval.to_string()))?;
+                    write!(f, "/{}", time.map_or(String::new(), |time| time.to_string()))?;
+                },
+            }
+            write!(f, "]")?;
+        }
+
+        write!(f, ";)")
+    }
+}
+
+//#################################################################################################
+//
+//                                       GGF TESTS
+//
+//#################################################################################################
+
+/*
+ * Checks the reading of games as written by GGS, and round trips from unusual starting positions.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_ggs_game() {
+        let ggf: GgfGame = "(;GM[Othello]PC[GGS/os]DT[2003.12.15_13:24:03.MST]PB[alice]PW[bob]\
+            RB[2197.01]RW[2177.55]TI[05:00//02:00]TY[8]RE[+18.000:r]\
+            BO[8 -------- -------- -------- ---O*--- ---*O--- -------- -------- -------- *]\
+            B[f5//0.01]W[d6/-1.50/2.50]B[C3];)".parse().unwrap();
+
+        let players = (ggf.place.as_str(), ggf.black.as_str(), ggf.white.as_str());
+        assert_eq!(players, ("GGS/os", "alice", "bob"));
+        let ratings = (ggf.black_rating, ggf.white_rating, ggf.result);
+        assert_eq!(ratings, (Some(2197.01), Some(2177.55), Some(18.0)));
+        assert_eq!(ggf.time_control, "05:00//02:00");
+        let d6 = GgfMove {
+            color: Color::White,
+            mv: Move::Square(43),
+            eval: Some(-1.5),
+            time: Some(2.5),
+        };
+        assert_eq!(ggf.moves[1], d6);
+        assert_eq!(ggf.to_game().position(), Game::from_transcript("f5d6c3").unwrap().position());
+        assert_eq!(ggf.to_string().parse::<GgfGame>(), Ok(ggf));
+
+        assert!("(;GM[Othello]B[f5]B[d6];)".parse::<GgfGame>().is_err());
+        assert!("(;GM[Othello]BO[8 ---- *];)".parse::<GgfGame>().is_err());
+        assert!("(;GM[Chess]B[f5];)".parse::<GgfGame>().is_err());
+        assert!("(;GM[Othello]PB[alice;)".parse::<GgfGame>().is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        // White, on b1, has to pass before Black plays c1 and ends the game.
+        let start = Position::create(Othello::create(1 << 0, 1 << 1), Color::White);
+        let mut game = Game::create(start);
+        assert!(game.play(0) && game.play(1 << 2));
+
+        let mut ggf = GgfGame::from_game(&game);
+        ggf.black = "a [bracketed] \\ name".to_owned();
+        ggf.moves[1].eval = Some(3.0);
+        assert_eq!(ggf.result, Some(3.0));
+
+        let read: GgfGame = ggf.to_string().parse().unwrap();
+        assert_eq!(read, ggf);
+        assert_eq!(read.to_game().positions(), game.positions());
+
+        // The pass may be left out.
+        let implicit: GgfGame = ggf.to_string().replace("W[PA]", "").parse().unwrap();
+        assert_eq!(implicit.moves[0].mv, Move::Pass);
+    }
+
+    #[test]
+    fn unfinished_game() {
+        let ggf: GgfGame = "(;GM[Othello]PB[alice]PW[bob]RE[?]\
+            BO[8 ---------------------------O*------*O--------------------------- *]\
+            B[f5]W[d6];)".parse().unwrap();
+        assert_eq!(ggf.result, None);
+        assert_eq!(ggf.to_game().transcript(), "f5d6");
+
+        let read: GgfGame = ggf.to_string().parse().unwrap();
+        assert_eq!(read, ggf);
+        assert!(!ggf.to_string().contains("RE["));
+    }
+}
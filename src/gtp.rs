@@ -0,0 +1,297 @@
+use crate::types::*;
+use crate::othello::*;
+use crate::game::*;
+
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+//#################################################################################################
+//
+//                                       CONSTANTS
+//
+//#################################################################################################
+
+/*
+ * The name of the engine, as given by the "name" command.
+ */
+const NAME: &str = "Rust-Othello";
+
+/*
+ * The commands of the protocol, with their arguments and what they do, as listed by the
+ * "list_commands" and "help" commands.
+ */
+const COMMANDS: [(&str, &str); 15] = [
+    ("protocol_version", "gives the version of the protocol, 2"),
+    ("name", "gives the name of the engine"),
+    ("version", "gives the version of the engine"),
+    ("known_command <command>", "tells whether the command is known, \"true\" or \"false\""),
+    ("list_commands", "lists the commands, one per line"),
+    ("help", "lists the commands with their arguments and what they do"),
+    ("quit", "ends the session"),
+    ("boardsize <size>", "checks the size of the board, which can only be 8"),
+    ("clear_board", "starts a new game"),
+    ("play <color> <move>", "plays a move, as in \"play black f5\" or \"play white pass\""),
+    ("genmove <color>", "plays the move of the engine, as in \"f5\", \"pass\" or \"resign\""),
+    ("undo", "takes back the last move"),
+    ("showboard", "draws the board, X being black and O white, and tells the color to move"),
+    ("final_score", "gives the disc difference, as in \"B+12\", \"W+4\" or \"0\" for a draw"),
+    ("set_time <seconds>", "lets the engine think for the given time per move from now on"),
+];
+
+//#################################################################################################
+//
+//                                      GTP ENGINE
+//
+//#################################################################################################
+
+/*
+ * An engine speaking a text protocol modelled on the Go Text Protocol: commands are read one per
+ * line on the standard input, optionally preceded by a number echoed in the answer, and each
+ * answer, on the standard output, starts with '=' on success or '?' on failure and ends with an
+ * empty line. Empty lines and comments, starting with '#', are ignored. The engine plays with a
+ * player, created anew with the given function when the time per move is set.
+ */
+pub struct GtpEngine<F: Fn(Duration) -> Box<dyn Player>> {
+    create: F,
+    player: Box<dyn Player>,
+    game: Game,
+}
+
+/*
+ * Parses a color, as in "black", "b", "white" or "w", in any case.
+ */
+fn parse_color(s: &str) -> Result<Color, String> {
+    match s.to_lowercase().as_str() {
+        "black" | "b" => Ok(Color::Black),
+        "white" | "w" => Ok(Color::White),
+        _ => Err(format!("invalid color \"{}\"", s)),
+    }
+}
+
+/*
+ * Draws the board, row by row, with the coordinates around.
+ */
+fn draw_board(oth: &Othello) -> String {
+    let mut board: String = "  a b c d e f g h".to_owned();
+
+    for y in 0..8 {
+        board += &format!("\n{}", y + 1);
+        for x in 0..8 {
+            board += match oth.get_square(x, y) {
+                Square::Black => " X",
+                Square::White => " O",
+                Square::Empty => " -",
+            };
+        }
+    }
+
+    board
+}
+
+impl<F: Fn(Duration) -> Box<dyn Player>> GtpEngine<F> {
+    /*
+     * Creates a new GtpEngine playing with the given player until the time per move is set.
+     */
+    pub fn new(player: Box<dyn Player>, create: F) -> GtpEngine<F> {
+        GtpEngine { create, player, game: Game::new() }
+    }
+
+    /*
+     * Answers the commands read from the input until it ends or the "quit" command.
+     */
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (id, command) = match line.split_once(' ') {
+                Some((id, command)) if id.parse::<u32>().is_ok() => (id, command.trim()),
+                _ if line.parse::<u32>().is_ok() => (line, ""),
+                _ => ("", line),
+            };
+            let mut words = command.split_whitespace();
+            let name = words.next().unwrap_or("");
+            let args: Vec<&str> = words.collect();
+
+            match self.execute(name, &args) {
+                Ok(answer) => write!(output, "={} {}\n\n", id, answer)?,
+                Err(error) => write!(output, "?{} {}\n\n", id, error)?,
+            }
+            output.flush()?;
+
+            if name == "quit" {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Executes a command, returning the answer on success or the error on failure.
+     */
+    fn execute(&mut self, name: &str, args: &[&str]) -> Result<String, String> {
+        match (name, args) {
+            ("protocol_version", []) => Ok("2".to_owned()),
+            ("name", []) => Ok(NAME.to_owned()),
+            ("version", []) => Ok(env!("CARGO_PKG_VERSION").to_owned()),
+            ("known_command", [command]) => {
+                let known = COMMANDS.iter()
+                    .any(|(usage, _)| usage.split(' ').next() == Some(command));
+                Ok(known.to_string())
+            },
+            ("list_commands", []) => {
+                let names: Vec<&str> =
+                    COMMANDS.iter().map(|(usage, _)| usage.split(' ').next().unwrap()).collect();
+                Ok(names.join("\n"))
+            },
+            ("help", []) => {
+                let lines: Vec<String> = COMMANDS.iter()
+                    .map(|(usage, help)| format!("{:<24} {}", usage, help))
+                    .collect();
+                Ok(lines.join("\n"))
+            },
+            ("quit", []) => Ok(String::new()),
+            ("boardsize", [size]) => match *size {
+                "8" => Ok(String::new()),
+                _ => Err("unacceptable size".to_owned()),
+            },
+            ("clear_board", []) => {
+                self.game = Game::new();
+                Ok(String::new())
+            },
+            ("play", [color, mv]) => {
+                let color = parse_color(color)?;
+                let mv: Move = mv.parse().map_err(|err: ParseMoveError| err.to_string())?;
+                self.pass_until(color)?;
+                if self.game.play(mv.into()) {
+                    Ok(String::new())
+                } else {
+                    Err("illegal move".to_owned())
+                }
+            },
+            ("genmove", [color]) => {
+                let color = parse_color(color)?;
+                self.pass_until(color)?;
+                if self.game.position().legal_moves() == 0 {
+                    return Ok("pass".to_owned());
+                }
+                match self.player.act(&self.game) {
+                    Action::Play(result) if self.game.play(result.best_move) => {
+                        Ok(Move::from(result.best_move).to_string())
+                    },
+                    Action::Resign => Ok("resign".to_owned()),
+                    _ => Err("the player did not play a move".to_owned()),
+                }
+            },
+            ("undo", []) => {
+                if !self.game.undo() {
+                    return Err("cannot undo".to_owned());
+                }
+                // Passes played on the way are taken back along with the move.
+                while self.game.moves().last() == Some(&0) && self.game.undo() {}
+                Ok(String::new())
+            },
+            ("showboard", []) => {
+                let pos = self.game.position();
+                let to_move = if pos.side_to_move() == Color::Black { "black" } else { "white" };
+                Ok(format!("\n{}\n{} to move", draw_board(&pos.board()), to_move))
+            },
+            ("final_score", []) => {
+                let score = self.game.position().board().score();
+                let diff = score.get(Color::Black) as i32 - score.get(Color::White) as i32;
+                Ok(match diff {
+                    0 => "0".to_owned(),
+                    diff if diff > 0 => format!("B+{}", diff),
+                    diff => format!("W+{}", -diff),
+                })
+            },
+            ("set_time", [seconds]) => match seconds.parse::<f64>() {
+                Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                    self.player = (self.create)(Duration::from_secs_f64(seconds));
+                    Ok(String::new())
+                },
+                _ => Err(format!("invalid time \"{}\"", seconds)),
+            },
+            _ if COMMANDS.iter().any(|(usage, _)| usage.split(' ').next() == Some(name)) => {
+                Err("wrong number of arguments".to_owned())
+            },
+            _ => Err("unknown command".to_owned()),
+        }
+    }
+
+    /*
+     * Makes it the turn of the given color, passing for the other one if it has to. Fails if the
+     * other color is to move and has a legal move.
+     */
+    fn pass_until(&mut self, color: Color) -> Result<(), String> {
+        if self.game.position().side_to_move() != color && !self.game.play(0) {
+            return Err(format!("not the turn of {:?}", color).to_lowercase());
+        }
+        Ok(())
+    }
+}
+
+//#################################################################################################
+//
+//                                       GTP TEST
+//
+//#################################################################################################
+
+/*
+ * Checks the answers to a scripted session.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alphabeta::*;
+
+    #[test]
+    fn session() {
+        let input = "1 boardsize 8\n\
+            play black f5\n\
+            # A comment.\n\
+            play black d6\n\
+            2 genmove white\n\
+            undo\n\
+            showboard\n\
+            final_score\n\
+            known_command genmove\n\
+            set_time fast\n\
+            frobnicate\n\
+            clear_board\n\
+            undo\n\
+            quit\n\
+            name\n";
+
+        let create = |_| {
+            Box::new(AlphaBetaPlayer::new(2).with_threads(1).with_hash_size(1)) as Box<dyn Player>
+        };
+        let mut engine = GtpEngine::new(create(Duration::from_secs(1)), create);
+        let mut output: Vec<u8> = vec![];
+        engine.run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let answers: Vec<&str> = output.split("\n\n").collect();
+
+        assert_eq!(answers[0], "=1 ");
+        assert_eq!(answers[1], "= ");
+        assert_eq!(answers[2], "? not the turn of black");
+        let mv: Move = answers[3].strip_prefix("=2 ").unwrap().parse().unwrap();
+        assert!(Game::from_transcript(&format!("f5{}", mv)).is_ok());
+        assert_eq!(answers[4], "= ");
+        assert!(answers[5].ends_with(
+            "5 - - - X X X - -\n6 - - - - - - - -\n\
+             7 - - - - - - - -\n8 - - - - - - - -\nwhite to move"
+        ));
+        assert_eq!(answers[6], "= B+3");
+        assert_eq!(answers[7], "= true");
+        assert!(answers[8].starts_with('?') && answers[9].starts_with('?'));
+        assert_eq!(answers[10], "= ");
+        assert_eq!(answers[11], "? cannot undo");
+        assert_eq!(answers[12], "= ");
+        assert_eq!(answers[13], "");
+    }
+}
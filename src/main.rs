@@ -1,11 +1,540 @@
 mod types;
 mod othello;
+mod files;
+mod position;
+mod game;
+mod ggf;
+mod wthor;
+mod nboard;
+mod gtp;
+mod external;
+mod tournament;
 mod terminal;
+mod transposition;
+mod endgame;
+mod ordering;
+mod eval;
+mod book;
 mod alphabeta;
+mod bench;
+mod train;
 
+use crate::types::*;
+use crate::position::*;
+use crate::game::*;
 use crate::terminal::*;
+use crate::ordering::*;
+use crate::eval::*;
+use crate::book::*;
 use crate::alphabeta::*;
+use crate::bench::*;
+use crate::train::*;
+use crate::wthor::*;
+use crate::nboard::*;
+use crate::gtp::*;
+use crate::external::*;
+use crate::tournament::*;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/*
+ * An option following an AI's description, holding the weights or the book it names once they
+ * are loaded, so that AIs created again from the same options share them.
+ */
+#[derive(Clone)]
+enum AiOption {
+    Hash(usize),
+    Endgame(u8),
+    Wld(u8),
+    Threads(usize),
+    Ordering(MoveOrdering),
+    Eval(Evaluator),
+    Book(Arc<Book>),
+    BookRandom(f32),
+}
+
+/*
+ * Reads the comma-separated "<option>=<value>" options following an AI's description, loading
+ * the files they name.
+ */
+fn parse_options<'a>(options: impl Iterator<Item = &'a str>) -> Option<Vec<AiOption>> {
+    options.map(|option| {
+        let (name, value) = option.split_once('=')?;
+        Some(match name {
+            "hash" => AiOption::Hash(value.parse().ok()?),
+            "endgame" => AiOption::Endgame(value.parse().ok()?),
+            "wld" => AiOption::Wld(value.parse().ok()?),
+            "threads" => AiOption::Threads(value.parse().ok()?),
+            "ordering" => AiOption::Ordering(value.parse().ok()?),
+            "eval" => AiOption::Eval(load_evaluator(value)?),
+            "book" => AiOption::Book(Arc::new(load_book(value)?)),
+            "bookrandom" => AiOption::BookRandom(value.parse().ok()?),
+            _ => return None,
+        })
+    }).collect()
+}
+
+/*
+ * Applies the given options to the AI.
+ */
+fn apply_options(mut ai: AlphaBetaPlayer, options: &[AiOption]) -> AlphaBetaPlayer {
+    for option in options {
+        ai = match option {
+            AiOption::Hash(megabytes) => ai.with_hash_size(*megabytes),
+            AiOption::Endgame(empties) => ai.with_endgame(*empties),
+            AiOption::Wld(empties) => ai.with_wld(*empties),
+            AiOption::Threads(threads) => ai.with_threads(*threads),
+            AiOption::Ordering(ordering) => ai.with_ordering(*ordering),
+            AiOption::Eval(evaluator) => ai.with_evaluator(evaluator.clone()),
+            AiOption::Book(book) => ai.with_book(Arc::clone(book)),
+            AiOption::BookRandom(randomness) => ai.with_book_randomness(*randomness),
+        };
+    }
+    ai
+}
+
+/*
+ * Loads the weights of the pattern evaluator from the given file, reporting why it failed if so.
+ */
+fn load_evaluator(path: &str) -> Option<Evaluator> {
+    match PatternWeights::load(path) {
+        Ok(weights) => Some(Evaluator::Patterns(Arc::new(weights))),
+        Err(err) => {
+            eprintln!("cannot load weights from {}: {}", path, err);
+            None
+        },
+    }
+}
+
+/*
+ * Loads the opening book from the given file, reporting why it failed if so.
+ */
+fn load_book(path: &str) -> Option<Book> {
+    Book::load(path).map_err(|err| eprintln!("cannot load book from {}: {}", path, err)).ok()
+}
+
+/*
+ * Reads the description of an AI on the command line, which is one of "depth:<plies>",
+ * "movetime:<seconds>" or "gametime:<seconds>", optionally followed by options (see USAGE).
+ * Returns the AI without its options, along with them.
+ */
+fn parse_ai_spec(spec: &str) -> Option<(AlphaBetaPlayer, Vec<AiOption>)> {
+    let mut options = spec.split(',');
+    let mut split = options.next()?.splitn(2, ':');
+    let kind = split.next()?;
+    let arg = split.next();
+
+    let seconds = |arg: Option<&str>| {
+        arg?.parse::<f64>().ok().filter(|s| *s > 0.0).map(Duration::from_secs_f64)
+    };
+
+    let ai = match kind {
+        "depth" => AlphaBetaPlayer::new(arg?.parse().ok()?),
+        "movetime" => AlphaBetaPlayer::with_time(TimeControl::PerMove(seconds(arg)?)),
+        "gametime" => AlphaBetaPlayer::with_time(TimeControl::PerGame(seconds(arg)?)),
+        _ => return None,
+    };
+
+    Some((ai, parse_options(options)?))
+}
+
+/*
+ * Creates an AI from its description on the command line.
+ */
+fn parse_ai(spec: &str) -> Option<AlphaBetaPlayer> {
+    let (ai, options) = parse_ai_spec(spec)?;
+    Some(apply_options(ai, &options))
+}
+
+/*
+ * Creates a player from its description on the command line, which is either "human" or the
+ * description of an AI.
+ */
+fn parse_player(spec: &str) -> Option<Box<dyn Player>> {
+    if spec == "human" {
+        Some(Box::new(TerminalPlayer))
+    } else if let Some(engine) = spec.strip_prefix("engine:") {
+        Some(Box::new(parse_engine(engine)?))
+    } else {
+        Some(Box::new(parse_ai(spec)?))
+    }
+}
+
+/*
+ * The time an external engine has for each move when none is given.
+ */
+const DEFAULT_ENGINE_TIMEOUT: f64 = 60.0;
+
+/*
+ * Reads the description of an external engine on the command line, which is its command line,
+ * optionally followed by the comma-separated "depth=<plies>" and "timeout=<seconds>" options.
+ */
+fn parse_engine_spec(spec: &str) -> Option<(&str, Option<u8>, Duration)> {
+    let mut options = spec.split(',');
+    let command = options.next().filter(|command| !command.trim().is_empty())?;
+    let (mut depth, mut timeout) = (None, DEFAULT_ENGINE_TIMEOUT);
+
+    for option in options {
+        match option.split_once('=')? {
+            ("depth", value) => depth = Some(value.parse().ok()?),
+            ("timeout", value) => {
+                timeout = value.parse().ok().filter(|&timeout: &f64| timeout > 0.0)?;
+            },
+            _ => return None,
+        }
+    }
+
+    Some((command, depth, Duration::from_secs_f64(timeout)))
+}
+
+/*
+ * Launches an external engine from its description on the command line, reporting why it failed
+ * if so.
+ */
+fn parse_engine(spec: &str) -> Option<ExternalEnginePlayer> {
+    let (command, depth, timeout) = parse_engine_spec(spec)?;
+    ExternalEnginePlayer::new(command, depth, timeout)
+        .map_err(|err| eprintln!("{}", err))
+        .ok()
+}
+
+/*
+ * Checks the description of a player able to play without a human, an AI or an external engine,
+ * without creating it, so that no engine is launched.
+ */
+fn is_headless_player(spec: &str) -> bool {
+    match spec.strip_prefix("engine:") {
+        Some(engine) => parse_engine_spec(engine).is_some(),
+        None => parse_ai(spec).is_some(),
+    }
+}
+
+/*
+ * Prints the analysis of every legal move of the position, from best to worst.
+ */
+fn print_analysis(ai: &AlphaBetaPlayer, pos: Position) {
+    for result in ai.analyze(pos) {
+        println!("{}", result);
+    }
+}
+
+/*
+ * The AI playing the self-play training games when none is given.
+ */
+const DEFAULT_TRAINING_AI: &str = "depth:4,threads=1";
+
+/*
+ * The AI solving the endgame test suite when none is given.
+ */
+const DEFAULT_ENDGAME_AI: &str = "depth:60,endgame=60";
+
+/*
+ * The AI playing through the text protocol when none is given.
+ */
+const DEFAULT_GTP_AI: &str = "depth:10";
+
+/*
+ * The AI analyzing the book positions when none is given.
+ */
+const DEFAULT_BOOK_AI: &str = "depth:8";
+
+/*
+ * Expands the opening book at the given path, or a new one if there is no such file, with the
+ * positions of the WTHOR games kept by the filter if given, and saves it.
+ */
+fn build_book(
+    path: &str,
+    plies: usize,
+    window: f32,
+    ai: &AlphaBetaPlayer,
+    wthor: Option<(Wthor, WthorFilter)>,
+) -> std::io::Result<()> {
+    let mut book = match Book::load(path) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Book::new(),
+        book => book?,
+    };
+    if let Some((wthor, filter)) = wthor {
+        book.add_positions(ai, wthor.positions(&filter), plies);
+    }
+    book.build(ai, plies, window);
+    book.save(path)?;
+    println!("book of {} positions saved to {}", book.len(), path);
+    Ok(())
+}
+
+/*
+ * Tells whether a command line argument is about a WTHOR database: a .wtb file or one of the
+ * "jou=", "trn=", "player=", "tournament=" and "year=" options.
+ */
+fn is_wthor_arg(arg: &str) -> bool {
+    let option = arg.split_once('=').map(|(name, _)| name);
+    arg.to_lowercase().ends_with(".wtb")
+        || matches!(option, Some("jou" | "trn" | "player" | "tournament" | "year"))
+}
+
+/*
+ * Loads the games of the WTHOR .wtb files among the given arguments, naming the players and
+ * tournaments from the .jou and .trn files if given, along with the filter given by the other
+ * options. There must be at least one .wtb file.
+ */
+fn load_wthor(args: &[&String]) -> std::io::Result<(Wthor, WthorFilter)> {
+    let (options, files): (Vec<&String>, Vec<&String>) =
+        args.iter().partition(|arg| arg.contains('='));
+    if files.is_empty() {
+        usage();
+    }
+
+    let (mut wthor, mut filter) = (Wthor::new(), WthorFilter::default());
+    for option in options {
+        match option.split_once('=').unwrap() {
+            ("player", name) => filter.player = Some(name.to_owned()),
+            ("tournament", name) => filter.tournament = Some(name.to_owned()),
+            ("year", year) => filter.year = Some(year.parse().unwrap_or_else(|_| usage())),
+            ("jou", path) => wthor.load_players(path)?,
+            ("trn", path) => wthor.load_tournaments(path)?,
+            _ => usage(),
+        }
+    }
+    for path in files {
+        for skipped in wthor.load_games(path)? {
+            eprintln!(
+                "skipping game {} of {}: illegal move {} at ply {}",
+                skipped.number, path, skipped.byte, skipped.ply,
+            );
+        }
+    }
+
+    Ok((wthor, filter))
+}
+
+/*
+ * Loads a WTHOR database as load_wthor does, exiting if it fails.
+ */
+fn load_wthor_or_exit(args: &[&String]) -> (Wthor, WthorFilter) {
+    load_wthor(args).unwrap_or_else(|err| {
+        eprintln!("reading WTHOR files failed: {}", err);
+        std::process::exit(1);
+    })
+}
 
 fn main() {
-    terminal_play(&AlphaBetaPlayer::new(9), &AlphaBetaPlayer::new(10));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [] => terminal_play(&AlphaBetaPlayer::new(9), &AlphaBetaPlayer::new(10)),
+        [cmd, rest @ ..] if cmd == "bench" => {
+            let default_specs = ["depth:10,threads=1".to_owned(), "depth:10".to_owned()];
+            let specs = if rest.is_empty() { &default_specs[..] } else { rest };
+            if specs.len() > 2 || specs.iter().any(|spec| parse_ai(spec).is_none()) {
+                usage();
+            }
+            run_bench(specs, |spec| parse_ai(spec).unwrap());
+        },
+        [cmd, path, rest @ ..] if cmd == "endgame" && rest.len() <= 1 => {
+            let ai = parse_ai(rest.first().map_or(DEFAULT_ENDGAME_AI, |ai| ai.as_str()))
+                .unwrap_or_else(|| usage());
+            match run_endgame_suite(path, &ai) {
+                Ok(true) => (),
+                Ok(false) => std::process::exit(1),
+                Err(err) => {
+                    eprintln!("endgame test suite failed: {}", err);
+                    std::process::exit(1);
+                },
+            }
+        },
+        [cmd, ai, rest @ ..] if cmd == "analyze" && rest.len() <= 1 => {
+            let ai = parse_ai(ai).unwrap_or_else(|| usage());
+            match parse_start(rest.first().map_or("", |moves| moves.as_str())) {
+                Ok(pos) => print_analysis(&ai, pos),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                },
+            }
+        },
+        [cmd, weights, rest @ ..]
+            if cmd == "train" && rest.first().is_some_and(|arg| is_wthor_arg(arg)) =>
+        {
+            if !rest.iter().all(|arg| is_wthor_arg(arg)) {
+                usage();
+            }
+            let (wthor, filter) = load_wthor_or_exit(&rest.iter().collect::<Vec<&String>>());
+            if let Err(err) = run_training(TrainingGames::Wthor(wthor, filter), weights) {
+                eprintln!("training failed: {}", err);
+                std::process::exit(1);
+            }
+        },
+        [cmd, weights, games, rest @ ..] if cmd == "train" && rest.len() <= 1 => {
+            let games = match games.parse() {
+                Ok(count) => {
+                    let ai = parse_ai(rest.first().map_or(DEFAULT_TRAINING_AI, |ai| ai.as_str()));
+                    TrainingGames::SelfPlay(count, ai.unwrap_or_else(|| usage()))
+                },
+                Err(_) if rest.is_empty() => TrainingGames::Records(games.clone()),
+                Err(_) => usage(),
+            };
+            if let Err(err) = run_training(games, weights) {
+                eprintln!("training failed: {}", err);
+                std::process::exit(1);
+            }
+        },
+        [cmd, path, plies, window, rest @ ..] if cmd == "book" => {
+            let (wthor_args, rest): (Vec<&String>, Vec<&String>) =
+                rest.iter().partition(|arg| is_wthor_arg(arg));
+            if rest.len() > 1 {
+                usage();
+            }
+            let ai = parse_ai(rest.first().map_or(DEFAULT_BOOK_AI, |ai| ai.as_str()));
+            let wthor = Some(wthor_args)
+                .filter(|args| !args.is_empty())
+                .map(|args| load_wthor_or_exit(&args));
+            match (plies.parse(), window.parse(), ai) {
+                (Ok(plies), Ok(window), Some(ai)) => {
+                    if let Err(err) = build_book(path, plies, window, &ai, wthor) {
+                        eprintln!("book building failed: {}", err);
+                        std::process::exit(1);
+                    }
+                },
+                _ => usage(),
+            }
+        },
+        [cmd, rest @ ..] if cmd == "wthor" => {
+            if !rest.iter().all(|arg| is_wthor_arg(arg)) {
+                usage();
+            }
+            let (wthor, filter) = load_wthor_or_exit(&rest.iter().collect::<Vec<&String>>());
+            for game in wthor.games(&filter) {
+                println!("{}", wthor.to_ggf(game));
+            }
+            let games = wthor.games(&filter).count();
+            eprintln!("{} games, {} positions", games, wthor.positions(&filter).count());
+        },
+        [cmd, rest @ ..] if cmd == "nboard" && rest.len() <= 1 => {
+            let options = rest.first()
+                .map_or(Some(vec![]), |options| parse_options(options.split(',')))
+                .unwrap_or_else(|| usage());
+            // Setting the depth keeps the options, sharing the weights and the book.
+            let create = |depth: u8| apply_options(AlphaBetaPlayer::new(depth), &options);
+            let mut engine = NBoardEngine::new(create);
+            if let Err(err) = engine.run(std::io::stdin().lock(), std::io::stdout()) {
+                eprintln!("NBoard protocol failed: {}", err);
+                std::process::exit(1);
+            }
+        },
+        [cmd, rest @ ..] if cmd == "gtp" && rest.len() <= 1 => {
+            let spec = rest.first().map_or(DEFAULT_GTP_AI, |ai| ai.as_str());
+            let (ai, options) = parse_ai_spec(spec).unwrap_or_else(|| usage());
+            // Setting the time keeps the options of the AI, sharing the weights and the book.
+            let create = |time: Duration| {
+                let ai = AlphaBetaPlayer::with_time(TimeControl::PerMove(time));
+                Box::new(apply_options(ai, &options)) as Box<dyn Player>
+            };
+            let mut engine = GtpEngine::new(Box::new(apply_options(ai, &options)), create);
+            if let Err(err) = engine.run(std::io::stdin().lock(), std::io::stdout()) {
+                eprintln!("text protocol failed: {}", err);
+                std::process::exit(1);
+            }
+        },
+        [cmd, games, rest @ ..] if cmd == "match" => {
+            let (options, specs): (Vec<&String>, Vec<&String>) = rest.iter()
+                .partition(|arg| arg.starts_with("openings=") || arg.starts_with("ggf="));
+            let games: usize =
+                games.parse().ok().filter(|&games| games > 0).unwrap_or_else(|| usage());
+            if specs.len() < 2 || !specs.iter().all(|spec| is_headless_player(spec)) {
+                usage();
+            }
+            let specs: Vec<String> = specs.into_iter().cloned().collect();
+
+            let mut openings = vec![];
+            let mut archive = None;
+            for option in options {
+                match option.split_once('=').unwrap() {
+                    ("openings", path) => openings = read_openings(path).unwrap_or_else(|err| {
+                        eprintln!("cannot read openings from {}: {}", path, err);
+                        std::process::exit(1);
+                    }),
+                    (_, path) => archive = Some(path),
+                }
+            }
+
+            if let Err(err) = run_tournament(&specs, games, &openings, archive, parse_player) {
+                eprintln!("match failed: {}", err);
+                std::process::exit(1);
+            }
+        },
+        [black, white] => match (parse_player(black), parse_player(white)) {
+            (Some(black), Some(white)) => terminal_play(black.as_ref(), white.as_ref()),
+            _ => usage(),
+        },
+        _ => usage(),
+    }
+}
+
+const USAGE: &str = "\
+usage: othello [<black> <white>]
+       othello bench [<ai> [<ai>]]
+       othello endgame <suite> [<ai>]
+       othello analyze <ai> [<moves> | <position>]
+       othello train <weights> (<games> [<ai>] | <records> | <wthor>)
+       othello book <book> <plies> <window> [<ai>] [<wthor>]
+       othello nboard [<options>]
+       othello match <games> <player> <player>... [openings=<file>] [ggf=<file>]
+       othello gtp [<ai>]
+       othello wthor <wthor>
+
+where a player is one of: human, depth:<plies>, movetime:<seconds>, gametime:<seconds> or
+engine:<command>[,depth=<plies>][,timeout=<seconds>] for an external engine speaking the NBoard
+protocol (as in \"engine:edax -q -n 1 --nboard,timeout=10\"), which resigns if it fails to play,
+and an AI can be followed by comma-separated options:
+    ,hash=<megabytes>    size of the transposition table
+    ,endgame=<empties>   empty squares from which the game is solved exactly
+    ,wld=<empties>       empty squares from which a winning or drawing move is looked for
+    ,threads=<count>     number of searching threads
+    ,ordering=<sources>  move ordering, \"none\" or a '+'-separated list among
+                         hash, killers, fastest[<min depth>], history and squares
+    ,eval=<file>         weights of the pattern evaluator, instead of the square masks
+    ,book=<file>         opening book to play from
+    ,bookrandom=<discs>  how far below the best book move a book move may be picked at random
+
+and <wthor> is a WTHOR database, as one or more .wtb files followed by options:
+    jou=<file>           .jou file naming the players
+    trn=<file>           .trn file naming the tournaments
+    player=<name>        keeps the games of the players whose name contains this one
+    tournament=<name>    keeps the games of the tournaments whose name contains this one
+    year=<year>          keeps the games of that year
+
+bench searches a fixed set of positions with one or two AIs, to compare their times and nodes.
+endgame solves the positions of an endgame test suite, such as the FFO positions #40 to #59, with
+the AI (by default depth:60,endgame=60) and checks the scores and moves against the known ones. The
+suite holds one position per line as in Edax's OBF files: the position as for analyze followed by
+some moves with their scores, best moves first, as in \"...; G8:+18; H1:+12;\".
+analyze scores every legal move after the given moves (as in \"f5d6c3\") or of the given position,
+as its squares from a1 to h8 and the color to move (as in \"------...--X-O---...--- X\"), from best
+to worst.
+train fits the weights of the pattern evaluator, saved to <weights>, to the positions of either
+the given number of self-play games (by default with depth:4,threads=1) or the finished games of
+a records file, holding one game per line written as the moves of analyze or in GGF, or of the
+WTHOR database.
+book expands the opening book with the analysis by the AI (by default depth:8) of the positions up
+to the given number of plies, following the moves scoring at most <window> discs below the best
+one, after those of the games of the WTHOR database if given.
+match plays the given number of games between every two players other than human without showing
+them, colors alternating, an engine failing to start losing the game, from the openings of the
+file in turn if given, each played with both colors. The openings are written one per line as for
+analyze. It prints the wins, draws and losses, the average disc difference and the Elo difference
+with its 95% confidence interval of every pairing, the standings with more than two players, and
+appends the games in GGF to the ggf file if given.
+nboard speaks the NBoard protocol on the standard input and output, for the NBoard GUI, with an AI
+at the depth set by NBoard and the given comma-separated options (as in \"hash=64,eval=<file>\").
+gtp speaks a text protocol modelled on the Go Text Protocol on the standard input and output, with
+the AI (by default depth:10). The \"help\" command lists its commands.
+wthor prints in GGF the games of the WTHOR database, and how many games and positions there are.";
+
+/*
+ * Prints how to use the program and exits.
+ */
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
 }
@@ -0,0 +1,238 @@
+use crate::types::*;
+use crate::game::*;
+use crate::ggf::*;
+use crate::alphabeta::*;
+
+use std::io::{BufRead, Write};
+
+//#################################################################################################
+//
+//                                       CONSTANTS
+//
+//#################################################################################################
+
+/*
+ * The name of the engine, as shown by NBoard.
+ */
+const NAME: &str = "Rust-Othello";
+
+/*
+ * The depth of the AI until NBoard sets one.
+ */
+const DEFAULT_DEPTH: u8 = 10;
+
+//#################################################################################################
+//
+//                                     NBOARD ENGINE
+//
+//#################################################################################################
+
+/*
+ * An engine speaking the NBoard protocol: NBoard sends commands on the standard input, one per
+ * line, and reads the answers on the standard output. The engine keeps the game NBoard set up,
+ * updated by the moves NBoard sends, the engine's own moves included, and creates its AI anew
+ * for every depth NBoard asks for.
+ */
+pub struct NBoardEngine<F: Fn(u8) -> AlphaBetaPlayer> {
+    create: F,
+    ai: AlphaBetaPlayer,
+    game: Game,
+}
+
+impl<F: Fn(u8) -> AlphaBetaPlayer> NBoardEngine<F> {
+    /*
+     * Creates a new NBoardEngine, its AI being created at the given depth by the function.
+     */
+    pub fn new(create: F) -> NBoardEngine<F> {
+        let ai = create(DEFAULT_DEPTH);
+        NBoardEngine { create, ai, game: Game::new() }
+    }
+
+    /*
+     * Answers the commands read from the input until it ends or NBoard quits. Invalid commands
+     * are reported on the standard error and otherwise ignored.
+     */
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let (command, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+
+            match command {
+                "nboard" => writeln!(output, "set myname {}", NAME)?,
+                "set" => self.set(args.trim()),
+                "move" => self.play(args.trim()),
+                "go" => self.go(&mut output)?,
+                "hint" => match args.trim().parse() {
+                    Ok(count) => self.hint(count, &mut output)?,
+                    Err(_) => eprintln!("invalid hint count \"{}\"", args.trim()),
+                },
+                "ping" => writeln!(output, "pong {}", args.trim())?,
+                "learn" => writeln!(output, "learned")?,
+                "quit" => break,
+                "" => (),
+                _ => eprintln!("unknown command \"{}\"", line),
+            }
+
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Handles the "set depth <depth>" and "set game <ggf>" commands, other settings such as the
+     * contempt being ignored. A game that can't be read is reported and replaced by a new one,
+     * rather than searching the position of the previous game.
+     */
+    fn set(&mut self, args: &str) {
+        let (name, value) = args.split_once(' ').unwrap_or((args, ""));
+
+        match name {
+            "depth" => match value.trim().parse::<u8>() {
+                Ok(depth) if (1..=60).contains(&depth) => self.ai = (self.create)(depth),
+                _ => eprintln!("invalid depth \"{}\"", value.trim()),
+            },
+            "game" => match value.parse::<GgfGame>() {
+                Ok(ggf) => self.game = ggf.to_game(),
+                Err(err) => {
+                    eprintln!("{}, starting a new game", err);
+                    self.game = Game::new();
+                },
+            },
+            _ => (),
+        }
+    }
+
+    /*
+     * Handles the "move <move>[/<eval>[/<time>]]" command, playing the move in the game.
+     */
+    fn play(&mut self, args: &str) {
+        let mv = args.split('/').next().unwrap_or("");
+
+        match mv.parse::<Move>() {
+            Ok(mv) if self.game.play(mv.into()) => (),
+            Ok(mv) => eprintln!("illegal move {}", mv),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    /*
+     * Handles the "go" command, answering with the move of the AI in the current position, its
+     * score in discs and the time spent, as in "=== f5/1.50/0.42".
+     */
+    fn go(&mut self, output: &mut impl Write) -> std::io::Result<()> {
+        let pos = self.game.position();
+        if pos.legal_moves() == 0 {
+            return writeln!(output, "=== PA");
+        }
+
+        writeln!(output, "status Thinking")?;
+        output.flush()?;
+        let result = self.ai.think(pos);
+        writeln!(
+            output,
+            "=== {}/{:.2}/{:.2}",
+            Move::from(result.best_move), self.ai.discs(&result), result.elapsed.as_secs_f64(),
+        )?;
+        writeln!(output, "status")
+    }
+
+    /*
+     * Handles the "hint <count>" command, answering with the given number of best moves of the
+     * current position, each with its score in discs and the depth of its search, "100%" when
+     * it is exact, as in "search f5 1.50 0 10".
+     */
+    fn hint(&mut self, count: usize, output: &mut impl Write) -> std::io::Result<()> {
+        let pos = self.game.position();
+        if pos.legal_moves() == 0 {
+            return Ok(());
+        }
+
+        writeln!(output, "status Analyzing")?;
+        output.flush()?;
+        for result in self.ai.analyze(pos).iter().take(count) {
+            let depth = match result.kind {
+                ScoreKind::Exact => "100%".to_owned(),
+                _ => result.depth.to_string(),
+            };
+            let mv = Move::from(result.best_move);
+            writeln!(output, "search {} {:.2} 0 {}", mv, self.ai.discs(result), depth)?;
+        }
+        writeln!(output, "status")
+    }
+}
+
+//#################################################################################################
+//
+//                                      NBOARD TEST
+//
+//#################################################################################################
+
+/*
+ * Checks the answers to a session as NBoard would run it.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine() -> NBoardEngine<impl Fn(u8) -> AlphaBetaPlayer> {
+        NBoardEngine::new(|depth| AlphaBetaPlayer::new(depth).with_threads(1).with_hash_size(1))
+    }
+
+    #[test]
+    fn session() {
+        let input = "nboard 2\n\
+            set depth 2\n\
+            set game (;GM[Othello]\
+            BO[8 ---------------------------O*------*O--------------------------- *]B[f5];)\n\
+            ping 1\n\
+            go\n\
+            hint 2\n\
+            move d6/0.5/1.2\n\
+            move d6\n\
+            quit\n\
+            ping 2\n";
+
+        let mut engine = engine();
+        let mut output: Vec<u8> = vec![];
+        engine.run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], "set myname Rust-Othello");
+        assert_eq!(lines[1], "pong 1");
+        assert_eq!(lines[2], "status Thinking");
+        let answer = lines[3].strip_prefix("=== ").unwrap();
+        let mv: Move = answer.split('/').next().unwrap().parse().unwrap();
+        assert!(Game::from_transcript(&format!("f5{}", mv)).is_ok());
+        assert_eq!(lines.iter().filter(|line| line.starts_with("search ")).count(), 2);
+        assert!(!output.contains("pong 2"));
+
+        // The first "move d6" was played, the second one is illegal for Black.
+        assert_eq!(engine.game.transcript(), "f5d6");
+    }
+
+    #[test]
+    fn nboard_game() {
+        let input = "nboard 2\n\
+            set depth 2\n\
+            set game (;GM[Othello]PC[NBoard]DT[2014-02-21 20:52:27 GMT]PB[Rust-Othello]PW[chris]\
+            RE[?]TI[15:00]TY[8]\
+            BO[8 ---------------------------O*------*O--------------------------- *]\
+            B[F5]W[F6]B[E6]W[F4];)\n\
+            go\n";
+
+        let mut engine = engine();
+        let mut output: Vec<u8> = vec![];
+        engine.run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let answer = output.lines().find_map(|line| line.strip_prefix("=== ")).unwrap();
+        let mv: Move = answer.split('/').next().unwrap().parse().unwrap();
+        assert!(Game::from_transcript(&format!("f5f6e6f4{}", mv)).is_ok());
+
+        // A game that can't be read starts a new one.
+        engine.run("set game (;GM[Othello]B[d6];)\n".as_bytes(), std::io::sink()).unwrap();
+        assert_eq!(engine.game.transcript(), "");
+    }
+}
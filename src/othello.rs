@@ -43,6 +43,76 @@ macro_rules! south_east {
     ($x: ident) => { ($x & 0xFEFEFEFEFEFEFEFE).wrapping_shr(9) }
 }
 
+//#################################################################################################
+//
+//                                    ZOBRIST KEYS
+//
+//#################################################################################################
+
+/*
+ * A step of the splitmix64 generator, used to fill the Zobrist tables at compile time.
+ */
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    (state, z ^ (z >> 31))
+}
+
+/*
+ * Generates one random key per square and per color, then folds them into tables indexed by
+ * every possible value of each byte of a BitBoard, so that a board can be hashed with 16 lookups
+ * while still being the xor of the keys of all of its disks.
+ */
+const fn zobrist_keys() -> [[[u64; 256]; 8]; 2] {
+    let mut square_keys = [[0u64; 64]; 2];
+    let mut state: u64 = 0x0123456789ABCDEF;
+    let mut color = 0;
+    while color < 2 {
+        let mut sq = 0;
+        while sq < 64 {
+            let (next, key) = splitmix64(state);
+            state = next;
+            square_keys[color][sq] = key;
+            sq += 1;
+        }
+        color += 1;
+    }
+
+    let mut keys = [[[0u64; 256]; 8]; 2];
+    let mut color = 0;
+    while color < 2 {
+        let mut byte = 0;
+        while byte < 8 {
+            let mut value = 0;
+            while value < 256 {
+                let mut key = 0;
+                let mut bit = 0;
+                while bit < 8 {
+                    if value & (1 << bit) != 0 {
+                        key ^= square_keys[color][8 * byte + bit];
+                    }
+                    bit += 1;
+                }
+                keys[color][byte][value] = key;
+                value += 1;
+            }
+            byte += 1;
+        }
+        color += 1;
+    }
+
+    keys
+}
+
+static ZOBRIST_KEYS: [[[u64; 256]; 8]; 2] = zobrist_keys();
+
+/*
+ * The key xored into the hash when White is to move.
+ */
+const ZOBRIST_WHITE_TO_MOVE: u64 = splitmix64(0xFEDCBA9876543210).1;
+
 //#################################################################################################
 //
 //                                    OTHELLO TYPE
@@ -52,7 +122,7 @@ macro_rules! south_east {
 /*
  * An Othello board only needs two BitBoards. First BitBoard is White's and second is Black's.
  */
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Othello(BitBoard, BitBoard);
 
 impl Othello {
@@ -67,7 +137,7 @@ impl Othello {
      * Creates a new Othello with the given BitBoards.
      */
     #[inline(always)]
-    fn create(black: BitBoard, white: BitBoard) -> Othello {
+    pub fn create(black: BitBoard, white: BitBoard) -> Othello {
         Othello(black, white)
     }
 
@@ -188,6 +258,32 @@ impl Othello {
         }
     }
 
+//#################################################################################################
+//
+//                                        HASHING
+//
+//#################################################################################################
+
+    /*
+     * Returns the Zobrist hash of the board with the given color to move.
+     */
+    pub fn hash(&self, playing: Color) -> u64 {
+        let mut hash: u64 = match playing {
+            Color::Black => 0,
+            Color::White => ZOBRIST_WHITE_TO_MOVE,
+        };
+
+        let black = self.get_bitboard(Color::Black).to_le_bytes();
+        let white = self.get_bitboard(Color::White).to_le_bytes();
+
+        for byte in 0..8 {
+            hash ^= ZOBRIST_KEYS[0][byte][black[byte] as usize];
+            hash ^= ZOBRIST_KEYS[1][byte][white[byte] as usize];
+        }
+
+        hash
+    }
+
 //#################################################################################################
 //
 //                                        ACCESSERS
@@ -204,8 +300,43 @@ impl Othello {
 
         Score::new(black_score, white_score)
     }
+
+    /*
+     * Returns the BitBoard of the squares no disk has been played on yet.
+     */
+    #[inline(always)]
+    pub fn empty_squares(&self) -> BitBoard {
+        !(self.0 | self.1)
+    }
+
+//#################################################################################################
+//
+//                                      SYMMETRIES
+//
+//#################################################################################################
+
+    /*
+     * Returns the board transformed by the given symmetry.
+     */
+    #[inline(always)]
+    pub fn transform(&self, symmetry: Symmetry) -> Othello {
+        Othello::create(self.0.transform(symmetry), self.1.transform(symmetry))
+    }
+
+    /*
+     * Returns the canonical form of the board, the smallest of its eight symmetric boards, along
+     * with the symmetry turning the board into it. Moves of the canonical board are mapped back
+     * to this board by the inverse of the symmetry.
+     */
+    pub fn canonical(&self) -> (Othello, Symmetry) {
+        Symmetry::all()
+            .map(|symmetry| (self.transform(symmetry), symmetry))
+            .min_by_key(|(oth, _)| (oth.0, oth.1))
+            .unwrap()
+    }
 }
 
+
 //#################################################################################################
 //
 //                                     PERFT TEST
@@ -218,6 +349,7 @@ impl Othello {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::position::*;
 
     // Change depth here.
     const DEPTH: usize = 10;
@@ -225,20 +357,16 @@ mod tests {
     /*
      * The perft function in itself, that counts the number of leaf nodes at depth 9.
      */
-    fn perft(oth: Othello, color: Color, depth: usize) -> u64 {
+    fn perft(pos: Position, depth: usize) -> u64 {
         if depth == 0 { return 1; }
+        if pos.is_game_over() { return 1; }
+        if pos.must_pass() { return perft(pos.pass(), depth-1); }
 
         let mut res: u64 = 0;
-        let mut moves: BitBoard = oth.gen_moves(color);
-
-        if moves == 0 {
-            moves = oth.gen_moves(color.invert());
-            if moves == 0 { return 1; }
-            return perft(oth, color.invert(), depth-1);
-        }
+        let mut moves: BitBoard = pos.legal_moves();
 
         while moves != 0 {
-            res += perft(oth.make_move(color, moves.pop_lsb()), color.invert(), depth-1)
+            res += perft(pos.play(moves.pop_lsb()), depth-1)
         }
 
         res
@@ -258,8 +386,44 @@ mod tests {
         assert!(DEPTH < perft_table.len(), "Depth must be at most {}", perft_table.len() - 1);
 
         eprintln!("ici!");
-        let res: u64 = perft(Othello::new(), Color::Black, DEPTH);
+        let res: u64 = perft(Position::new(), DEPTH);
 
         assert_eq!(res, perft_table[DEPTH], "Got an invalid perft value for a depth of {}", DEPTH);
     }
+
+    /*
+     * Checks the symmetries of BitBoards against the ones of squares, and that transforming a
+     * game transforms its moves.
+     */
+    #[test]
+    fn symmetries() {
+        for symmetry in Symmetry::all() {
+            for sq in 0..64 {
+                let b: BitBoard = 1 << sq;
+                assert_eq!(b.transform(symmetry), 1 << symmetry.transform_square(sq));
+                assert_eq!(b.transform(symmetry).transform(symmetry.inverse()), b);
+            }
+        }
+
+        let mut oth = Othello::new();
+        let mut color = Color::Black;
+        let (canonical, symmetry) = oth.canonical();
+        assert_eq!(canonical.transform(symmetry.inverse()), oth);
+
+        for _ in 0..20 {
+            let moves = oth.gen_moves(color);
+            let mv = moves & moves.wrapping_neg();
+            for symmetry in Symmetry::all() {
+                let symmetric = oth.transform(symmetry);
+                assert_eq!(symmetric.gen_moves(color), moves.transform(symmetry));
+                assert_eq!(
+                    symmetric.make_move(color, mv.transform(symmetry)),
+                    oth.make_move(color, mv).transform(symmetry),
+                );
+                assert_eq!(symmetric.canonical().0, oth.canonical().0);
+            }
+            oth = oth.make_move(color, mv);
+            color = color.invert();
+        }
+    }
 }
@@ -0,0 +1,253 @@
+use crate::types::*;
+use crate::othello::*;
+
+//#################################################################################################
+//
+//                                     POSITION TYPE
+//
+//#################################################################################################
+
+/*
+ * A position of a game: the board and the color to move. The rules about passing and the end of
+ * the game live here: a color with no legal move has to pass, and the game is over once neither
+ * color can move.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Position {
+    board: Othello,
+    to_move: Color,
+}
+
+impl Position {
+    /*
+     * Creates the starting position of a game, Black moving first.
+     */
+    pub fn new() -> Position {
+        Position { board: Othello::new(), to_move: Color::Black }
+    }
+
+    /*
+     * Creates a position from a board and the color to move.
+     */
+    #[inline(always)]
+    pub fn create(board: Othello, to_move: Color) -> Position {
+        Position { board, to_move }
+    }
+
+    /*
+     * Returns the board of the position.
+     */
+    #[inline(always)]
+    pub fn board(&self) -> Othello {
+        self.board
+    }
+
+    /*
+     * Returns the color to move.
+     */
+    #[inline(always)]
+    pub fn side_to_move(&self) -> Color {
+        self.to_move
+    }
+
+    /*
+     * Returns the legal moves of the color to move.
+     */
+    #[inline(always)]
+    pub fn legal_moves(&self) -> BitBoard {
+        self.board.gen_moves(self.to_move)
+    }
+
+    /*
+     * Returns the position after the color to move plays the given legal move.
+     */
+    #[inline(always)]
+    pub fn play(&self, mv: BitBoard) -> Position {
+        debug_assert!(self.legal_moves() & mv != 0, "Illegal move");
+        Position { board: self.board.make_move(self.to_move, mv), to_move: self.to_move.invert() }
+    }
+
+    /*
+     * Returns the position after the color to move passes, which it may only do when it has no
+     * legal move.
+     */
+    #[inline(always)]
+    pub fn pass(&self) -> Position {
+        debug_assert!(self.legal_moves() == 0, "Pass with legal moves");
+        Position { board: self.board, to_move: self.to_move.invert() }
+    }
+
+    /*
+     * Returns true if the color to move has to pass: it has no legal move but its opponent has.
+     */
+    #[inline(always)]
+    pub fn must_pass(&self) -> bool {
+        self.legal_moves() == 0 && self.board.gen_moves(self.to_move.invert()) != 0
+    }
+
+    /*
+     * Returns true if neither color can move anymore.
+     */
+    #[inline(always)]
+    pub fn is_game_over(&self) -> bool {
+        self.legal_moves() == 0 && self.board.gen_moves(self.to_move.invert()) == 0
+    }
+
+    /*
+     * Returns the final score of the game if it is over.
+     */
+    pub fn result(&self) -> Option<Score> {
+        Some(self.board.score()).filter(|_| self.is_game_over())
+    }
+
+    /*
+     * Returns the number of empty squares.
+     */
+    #[inline(always)]
+    pub fn empties(&self) -> u8 {
+        self.board.empty_squares().pop_cnt()
+    }
+
+    /*
+     * Returns the Zobrist hash of the position.
+     */
+    #[inline(always)]
+    pub fn hash(&self) -> u64 {
+        self.board.hash(self.to_move)
+    }
+}
+
+//#################################################################################################
+//
+//                                     TEXT FORMAT
+//
+//#################################################################################################
+
+/*
+ * The error returned when parsing an invalid position, describing what is wrong.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParsePositionError(String);
+
+impl std::fmt::Display for ParsePositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid position: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePositionError {}
+
+/*
+ * Returns the color of a square or of the color to move in the text format, 'X' (or 'x' or '*')
+ * being black and 'O' (or 'o' or '0') white.
+ */
+fn parse_color(c: char) -> Option<Color> {
+    match c {
+        'X' | 'x' | '*' => Some(Color::Black),
+        'O' | 'o' | '0' => Some(Color::White),
+        _ => None,
+    }
+}
+
+impl std::str::FromStr for Position {
+    type Err = ParsePositionError;
+
+    /*
+     * Parses a position in the format of Edax's OBF files: its 64 squares from a1 to h8, row by
+     * row, 'X' being black, 'O' white and '-' (or '.') empty, then the color to move, 'X' or 'O',
+     * possibly followed by a ';'. Whitespace is ignored.
+     */
+    fn from_str(s: &str) -> Result<Position, ParsePositionError> {
+        let s = s.trim();
+        let chars: Vec<char> =
+            s.strip_suffix(';').unwrap_or(s).chars().filter(|c| !c.is_whitespace()).collect();
+        if chars.len() != 65 {
+            return Err(ParsePositionError(format!(
+                "expected 64 squares and the color to move, found {} characters", chars.len(),
+            )));
+        }
+
+        let (mut black, mut white): (BitBoard, BitBoard) = (0, 0);
+        for (sq, &c) in chars[..64].iter().enumerate() {
+            match parse_color(c) {
+                Some(Color::Black) => black |= 1 << sq,
+                Some(Color::White) => white |= 1 << sq,
+                None if c == '-' || c == '.' => (),
+                None => {
+                    let msg = format!("invalid square '{}' at {}", c, Move::Square(sq as u8));
+                    return Err(ParsePositionError(msg));
+                },
+            }
+        }
+
+        let to_move = parse_color(chars[64])
+            .ok_or_else(|| ParsePositionError(format!("invalid color to move '{}'", chars[64])))?;
+
+        Ok(Position::create(Othello::create(black, white), to_move))
+    }
+}
+
+impl std::fmt::Display for Position {
+    /*
+     * Formats the position in the format read by Position::from_str, as in
+     * "---------------------------OX------XO--------------------------- X".
+     */
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for y in 0..8 {
+            for x in 0..8 {
+                match self.board.get_square(x, y) {
+                    Square::Black => write!(f, "X")?,
+                    Square::White => write!(f, "O")?,
+                    Square::Empty => write!(f, "-")?,
+                }
+            }
+        }
+        write!(f, " {}", if self.to_move == Color::Black { 'X' } else { 'O' })
+    }
+}
+
+//#################################################################################################
+//
+//                                     POSITION TEST
+//
+//#################################################################################################
+
+/*
+ * Checks the rules about passing and the end of the game, and the text format.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_and_game_over() {
+        let start = Position::new();
+        assert!(!start.must_pass() && !start.is_game_over() && start.result().is_none());
+        assert_eq!(start.play(1 << 37).side_to_move(), Color::White);
+
+        // White, on b1, cannot capture Black's corner, while Black can play c1.
+        let pos = Position::create(Othello::create(1 << 0, 1 << 1), Color::White);
+        assert!(pos.must_pass() && !pos.is_game_over());
+        assert_eq!(pos.pass().legal_moves(), 1 << 2);
+
+        let over = pos.pass().play(1 << 2);
+        assert!(over.is_game_over() && !over.must_pass());
+        assert_eq!(over.result().unwrap().get(Color::Black), 3);
+    }
+
+    #[test]
+    fn text_format() {
+        let start = "---------------------------OX------XO--------------------------- X";
+        assert_eq!(start.parse::<Position>(), Ok(Position::new()));
+        assert_eq!(Position::new().to_string(), start);
+
+        let pos = Position::new().play(1 << 37).play(1 << 43);
+        assert_eq!(pos.to_string().parse::<Position>(), Ok(pos));
+        let dotted = "........ ........ ........ ...ox... ...xo... ........ ........ ........ x;";
+        assert_eq!(dotted.parse::<Position>(), Ok(Position::new()));
+
+        assert!(start[1..].parse::<Position>().is_err());
+        assert!(format!("{}-", start).parse::<Position>().is_err());
+        assert!(start.replace('X', "Y").parse::<Position>().is_err());
+    }
+}
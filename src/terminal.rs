@@ -1,5 +1,8 @@
 use crate::types::*;
 use crate::othello::*;
+use crate::position::*;
+use crate::game::*;
+use crate::ggf::*;
 
 //#################################################################################################
 //
@@ -87,18 +90,17 @@ pub fn print_oth(oth: &Othello, moves: BitBoard, mv: BitBoard) {
 
 pub struct TerminalPlayer;
 
-impl Player for TerminalPlayer {
+impl TerminalPlayer {
     /*
-     * Gets a user input of the form "[a-h][1-8]\n" and verifies it's validity. Converts The
-     * input to a BitBoard representing the desired move and checks that it is present in
-     * the moves BitBoard.
+     * Asks the user for a move until it is legal, telling what is wrong with the invalid ones.
+     * When given the game, also accepts the "undo", "redo" and "jump <ply>" commands moving
+     * through its history.
      */
-    fn chose_move(&self, _oth: Othello, moves: BitBoard, color: Color) -> BitBoard {
+    fn read_action(&self, pos: Position, game: Option<&Game>) -> Action {
+        let moves: BitBoard = pos.legal_moves();
         let mut input: String = String::new();
-        let mut bytes: &[u8];
-        let mut mv: BitBoard;
 
-        if color == Color::Black {
+        if pos.side_to_move() == Color::Black {
             blue!("X player");
         } else {
             cyan!("O player");
@@ -106,7 +108,13 @@ impl Player for TerminalPlayer {
 
         print!(", where do you want to ");
         yellow!("play ");
-        println!("? (regex format: \"[a-h][1-8]\\n\")");
+        println!("? (a square, as in \"f5\")");
+        if game.is_some() {
+            println!(
+                "You can also take back your move with \"undo\", replay it with \"redo\" or go to \
+                 a ply with \"jump <ply>\"."
+            );
+        }
 
         loop {
             input.clear();
@@ -115,16 +123,47 @@ impl Player for TerminalPlayer {
                 Err(_) => panic!("Couldn't read from the terminal."),
             }
 
-            bytes = input.as_bytes();
-            if bytes.len() != 3 { continue; }
-            if bytes[0] < 97 || bytes[1] < 49 { continue; }
-            mv = 8 * (bytes[1] as BitBoard - 49) + bytes[0] as BitBoard - 97;
-            if mv > 63 { continue; }
-            mv = 1u64 << mv;
-            if mv & moves != 0 { break; }
+            if let Some(game) = game {
+                match input.trim() {
+                    "undo" if game.ply() > 0 => return Action::Undo,
+                    "redo" if game.ply() < game.len() => return Action::Redo,
+                    command => {
+                        let ply = command.strip_prefix("jump ")
+                            .and_then(|ply| ply.trim().parse().ok());
+                        if let Some(ply) = ply.filter(|&ply| ply <= game.len()) {
+                            return Action::Jump(ply);
+                        }
+                    },
+                }
+            }
+
+            match input.parse::<Move>() {
+                Ok(mv) if BitBoard::from(mv) & moves != 0 => {
+                    return Action::Play(SearchResult::new(mv.into()));
+                },
+                Ok(mv) => println!("{} is not a legal move.", mv),
+                Err(err) => println!("{}.", err),
+            }
         }
+    }
+}
 
-        mv
+impl Player for TerminalPlayer {
+    /*
+     * Asks the user for a legal move.
+     */
+    fn chose_move(&self, pos: Position) -> BitBoard {
+        match self.read_action(pos, None) {
+            Action::Play(result) => result.best_move,
+            _ => unreachable!("Only moves are read without a game"),
+        }
+    }
+
+    /*
+     * Asks the user for a legal move, or a command moving through the history of the game.
+     */
+    fn act(&self, game: &Game) -> Action {
+        self.read_action(game.position(), Some(game))
     }
 }
 
@@ -134,49 +173,110 @@ impl Player for TerminalPlayer {
 //
 //#################################################################################################
 
+/*
+ * Prints what the player of the given color found while thinking about its last move, if it
+ * searched at all.
+ */
+fn print_thinking(thinking: &Option<(Color, SearchResult)>) {
+    if let Some((color, result)) = thinking.as_ref().filter(|(_, result)| result.nodes > 0) {
+        if *color == Color::Black {
+            blue!("X player");
+        } else {
+            cyan!("O player");
+        }
+        println!(" thought: {}\n", result);
+    }
+}
+
 /*
  * Play a game in the terminal, one player taking turn after the other, both being asked
- * what they want to play each time. Gives the score at the end of the game.
+ * what they want to play each time, and what the players found while thinking about their
+ * last move is shown under the board. Players may also take back their last move, replay it
+ * or go to any ply of the game, in which case the game goes on from there. A player may also
+ * resign. Gives the score, the transcript and the GGF record of the game at its end.
  */
 pub fn terminal_play(black: &dyn Player, white: &dyn Player) {
-    let mut oth: Othello = Othello::new();
-    let mut moves: BitBoard;
-    let mut mv: BitBoard = 0;
-    let mut color: Color = Color::Black;
+    let mut game: Game = Game::new();
+    let mut thinking: Option<(Color, SearchResult)> = None;
+    let mut resigned: Option<Color> = None;
 
     loop {
-        moves = oth.gen_moves(color);
-        if moves == 0 {
-            color = color.invert();
-            moves = oth.gen_moves(color);
-            if moves == 0 { break; }
+        if game.position().must_pass() {
+            game.play(0);
         }
 
-        print_oth(&oth, moves, mv);
-
-        if color == Color::Black {
-            mv = black.chose_move(oth, moves, Color::Black);
-        } else {
-            mv = white.chose_move(oth, moves, Color::White);
+        let pos: Position = game.position();
+        if pos.is_game_over() {
+            break;
         }
 
-        oth = oth.make_move(color, mv);
-        color = color.invert();
+        print_oth(&pos.board(), pos.legal_moves(), game.moves().last().copied().unwrap_or(0));
+        print_thinking(&thinking);
+
+        let color: Color = pos.side_to_move();
+        let player: &dyn Player = if color == Color::Black { black } else { white };
+        // Going through the history stops at the next position where the player has to move.
+        let players_turn = |game: &Game| {
+            game.position().side_to_move() == color && !game.position().must_pass()
+        };
+
+        match player.act(&game) {
+            Action::Play(result) => {
+                game.play(result.best_move);
+                thinking = Some((color, result));
+            },
+            Action::Undo => {
+                while game.undo() && !players_turn(&game) {}
+                thinking = None;
+            },
+            Action::Redo => {
+                while game.redo() && !players_turn(&game) {}
+                thinking = None;
+            },
+            Action::Jump(ply) => {
+                game.jump(ply);
+                thinking = None;
+            },
+            Action::Resign => {
+                resigned = Some(color);
+                break;
+            },
+        }
     }
 
-    print_oth(&oth, moves, mv);
-    let score: Score = oth.score();
-    print!("Game over! Final score is [");
-    blue!(format!("X: {}", score.get(Color::Black)));
-    print!(" - ");
-    cyan!(format!("O: {}", score.get(Color::White)));
-    println!("]");
-    if score.get(Color::Black) > score.get(Color::White) {
-        blue!("X player won ! Congatulations !\n");
-    } else if score.get(Color::Black) < score.get(Color::White) {
-        cyan!("O player won ! Congatulations !\n");
-    } else {
-        println!("It's a draw !");
+    let pos: Position = game.position();
+    print_oth(&pos.board(), 0, game.moves().last().copied().unwrap_or(0));
+    print_thinking(&thinking);
+    let mut ggf: GgfGame = GgfGame::from_game(&game);
+
+    match resigned {
+        Some(Color::Black) => {
+            print!("Game over! X player resigned. ");
+            cyan!("O player won ! Congatulations !\n");
+            ggf.result = Some(-64.0);
+        },
+        Some(Color::White) => {
+            print!("Game over! O player resigned. ");
+            blue!("X player won ! Congatulations !\n");
+            ggf.result = Some(64.0);
+        },
+        None => {
+            let score: Score = pos.result().unwrap();
+            print!("Game over! Final score is [");
+            blue!(format!("X: {}", score.get(Color::Black)));
+            print!(" - ");
+            cyan!(format!("O: {}", score.get(Color::White)));
+            println!("]");
+            if score.get(Color::Black) > score.get(Color::White) {
+                blue!("X player won ! Congatulations !\n");
+            } else if score.get(Color::Black) < score.get(Color::White) {
+                cyan!("O player won ! Congatulations !\n");
+            } else {
+                println!("It's a draw !");
+            }
+        },
     }
+    println!("Transcript: {}", game.transcript());
+    println!("GGF: {}", ggf);
     println!();
 }
@@ -0,0 +1,348 @@
+use crate::types::*;
+use crate::position::*;
+use crate::game::*;
+use crate::ggf::*;
+use crate::files::*;
+
+use std::io::{BufRead, Write};
+
+//#################################################################################################
+//
+//                                      HEADLESS GAME
+//
+//#################################################################################################
+
+/*
+ * The disc difference a game is scored with when a player resigns, as if it lost all its discs.
+ */
+const RESIGN_DIFF: i32 = 64;
+
+/*
+ * Plays a game between two players from the given position without showing anything, and
+ * returns it along with its disc difference from Black's point of view. A player resigning, or
+ * doing anything else than playing a legal move, loses the game.
+ */
+pub fn play_game(black: &dyn Player, white: &dyn Player, start: Position) -> (Game, i32) {
+    let mut game: Game = Game::create(start);
+
+    loop {
+        if game.position().must_pass() {
+            game.play(0);
+        }
+
+        let pos: Position = game.position();
+        if let Some(score) = pos.result() {
+            return (game, score.get(Color::Black) as i32 - score.get(Color::White) as i32);
+        }
+
+        let player: &dyn Player = if pos.side_to_move() == Color::Black { black } else { white };
+        let played = match player.act(&game) {
+            Action::Play(result) => game.play(result.best_move),
+            _ => false,
+        };
+        if !played {
+            let diff = if pos.side_to_move() == Color::Black { -RESIGN_DIFF } else { RESIGN_DIFF };
+            return (game, diff);
+        }
+    }
+}
+
+/*
+ * Plays a game between the players created from the given descriptions, as play_game does. A
+ * player that can't be created loses the game, as if it resigned, and the game is drawn if
+ * neither can.
+ */
+fn play_created(
+    create: &impl Fn(&str) -> Option<Box<dyn Player>>,
+    black: &str,
+    white: &str,
+    start: Position,
+) -> (Game, i32) {
+    match (create(black), create(white)) {
+        (Some(black), Some(white)) => play_game(black.as_ref(), white.as_ref(), start),
+        (None, None) => (Game::create(start), 0),
+        (None, _) => (Game::create(start), -RESIGN_DIFF),
+        (_, None) => (Game::create(start), RESIGN_DIFF),
+    }
+}
+
+/*
+ * Reads the starting positions of the games from a file holding one per line, either as the
+ * moves leading to it or in the text format. Empty lines and lines starting with '%' or '#' are
+ * skipped.
+ */
+pub fn read_openings(path: &str) -> std::io::Result<Vec<Position>> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut openings: Vec<Position> = vec![];
+
+    for (i, line) in file.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with(['%', '#']) {
+            continue;
+        }
+
+        let invalid = |msg: String| invalid_data(format!("line {}: {}", i + 1, msg));
+        let pos = parse_start(line.trim()).map_err(invalid)?;
+        if pos.is_game_over() {
+            return Err(invalid("the game is over".to_owned()));
+        }
+        openings.push(pos);
+    }
+
+    Ok(openings)
+}
+
+//#################################################################################################
+//
+//                                       STATISTICS
+//
+//#################################################################################################
+
+/*
+ * The results of the games of a player against another: its wins, draws and losses, and the sum
+ * of the disc differences.
+ */
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+struct Stats {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+    discs: i32,
+}
+
+impl Stats {
+    /*
+     * Adds a game, ended with the given disc difference from the point of view of the player.
+     */
+    fn add(&mut self, diff: i32) {
+        match diff.signum() {
+            1 => self.wins += 1,
+            0 => self.draws += 1,
+            _ => self.losses += 1,
+        }
+        self.discs += diff;
+    }
+
+    /*
+     * Returns the results of both stats together.
+     */
+    fn merge(&self, other: &Stats) -> Stats {
+        Stats {
+            wins: self.wins + other.wins,
+            draws: self.draws + other.draws,
+            losses: self.losses + other.losses,
+            discs: self.discs + other.discs,
+        }
+    }
+
+    /*
+     * Returns the results from the point of view of the opponent.
+     */
+    fn inverse(&self) -> Stats {
+        Stats { wins: self.losses, draws: self.draws, losses: self.wins, discs: -self.discs }
+    }
+
+    /*
+     * Returns the number of games.
+     */
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /*
+     * Returns the points scored, a win being worth 1 and a draw 1/2.
+     */
+    fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    /*
+     * Returns the Elo difference with the opponent matching the score, along with the bounds of
+     * its 95% confidence interval, from the standard deviation of the score of a game. A player
+     * never losing, or never winning, is infinitely stronger, or weaker.
+     */
+    fn elo(&self) -> (f64, f64, f64) {
+        let n = self.games() as f64;
+        let score = self.points() / n;
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2)) / n;
+        let margin = 1.96 * (variance / n).sqrt();
+
+        // Adding 0 turns the -0 of an even score into 0.
+        let elo = |score: f64| 0.0 - 400.0 * (1.0 / score.clamp(0.0, 1.0) - 1.0).log10();
+        (elo(score), elo(score - margin), elo(score + margin))
+    }
+}
+
+impl std::fmt::Display for Stats {
+    /*
+     * Formats the results, as in "+12 =3 -5, average disc difference +4.20, Elo +125.0 (+10.4,
+     * +270.6)".
+     */
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (elo, low, high) = self.elo();
+        let average = self.discs as f64 / self.games() as f64;
+        write!(f, "+{} ={} -{}, average disc difference {:+.2}, Elo {:+.1} ({:+.1}, {:+.1})",
+            self.wins, self.draws, self.losses, average, elo, low, high)
+    }
+}
+
+//#################################################################################################
+//
+//                                       TOURNAMENT
+//
+//#################################################################################################
+
+/*
+ * Plays a round-robin tournament between the players created from the given descriptions, which
+ * is a match when there are two of them: every player plays the given number of games against
+ * every other one, with a fresh player for each game, colors alternating. Every player is first
+ * created once, failing if one can't be, and a player that can't be created later on loses the
+ * game. The games start from the given openings in turn, each opening being played with both
+ * colors, or from the starting position if there are none. Prints the result of every game, the
+ * statistics and Elo difference of every pairing, and the standings, and appends every game in
+ * GGF to the archive file if given.
+ */
+pub fn run_tournament(
+    specs: &[String],
+    games: usize,
+    openings: &[Position],
+    archive: Option<&str>,
+    create: impl Fn(&str) -> Option<Box<dyn Player>>,
+) -> std::io::Result<()> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+    if games == 0 {
+        return Err(invalid("no games to play".to_owned()));
+    }
+    if let Some(spec) = specs.iter().find(|spec| create(spec).is_none()) {
+        return Err(invalid(format!("cannot create player {}", spec)));
+    }
+
+    let mut archive = match archive {
+        Some(path) => Some(std::fs::OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+    let mut totals: Vec<Stats> = vec![Stats::default(); specs.len()];
+
+    for i in 0..specs.len() {
+        for j in i + 1..specs.len() {
+            let mut stats = Stats::default();
+
+            for game in 0..games {
+                let start = openings.get(game / 2 % openings.len().max(1))
+                    .copied()
+                    .unwrap_or_else(Position::new);
+                let (black, white) = if game % 2 == 0 { (i, j) } else { (j, i) };
+
+                let (played, diff) = play_created(&create, &specs[black], &specs[white], start);
+                stats.add(if black == i { diff } else { -diff });
+                println!(
+                    "game {}/{}: {} (black) - {} (white): {:+}",
+                    game + 1, games, specs[black], specs[white], diff,
+                );
+
+                if let Some(file) = &mut archive {
+                    let mut ggf = GgfGame::from_game(&played);
+                    ggf.black = specs[black].clone();
+                    ggf.white = specs[white].clone();
+                    ggf.result = Some(diff as f32);
+                    writeln!(file, "{}", ggf)?;
+                }
+            }
+
+            println!("{} vs {}: {}", specs[i], specs[j], stats);
+            totals[i] = totals[i].merge(&stats);
+            totals[j] = totals[j].merge(&stats.inverse());
+        }
+    }
+
+    if specs.len() > 2 {
+        let mut ranking: Vec<usize> = (0..specs.len()).collect();
+        ranking.sort_by(|&a, &b| totals[b].points().total_cmp(&totals[a].points()));
+
+        println!("standings:");
+        for (rank, &player) in ranking.iter().enumerate() {
+            let total = &totals[player];
+            println!("{:>3}. {}: {} points, {}", rank + 1, specs[player], total.points(), total);
+        }
+    }
+
+    Ok(())
+}
+
+//#################################################################################################
+//
+//                                    TOURNAMENT TESTS
+//
+//#################################################################################################
+
+/*
+ * Checks the statistics and Elo estimation, and headless games.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alphabeta::*;
+
+    #[test]
+    fn elo() {
+        let mut stats = Stats::default();
+        for diff in [10, 2, -4, 0] {
+            stats.add(diff);
+        }
+        assert_eq!(stats, Stats { wins: 2, draws: 1, losses: 1, discs: 8 });
+        assert_eq!(stats.inverse().inverse(), stats);
+
+        // A score of 62.5% is worth about 89 Elo.
+        let (elo, low, high) = stats.elo();
+        assert!((elo - 88.7).abs() < 0.1 && low < elo && elo < high);
+        let (inverse, _, _) = stats.inverse().elo();
+        assert!((elo + inverse).abs() < 1e-9);
+
+        let perfect = Stats { wins: 3, draws: 0, losses: 0, discs: 60 };
+        assert_eq!(perfect.elo().0, f64::INFINITY);
+    }
+
+    #[test]
+    fn headless_games() {
+        let ai = || AlphaBetaPlayer::new(1).with_threads(1).with_hash_size(1);
+        let (game, diff) = play_game(&ai(), &ai(), Position::new());
+        let score = game.position().result().unwrap();
+        assert_eq!(diff, score.get(Color::Black) as i32 - score.get(Color::White) as i32);
+
+        // Resigning loses the game.
+        struct Resigner;
+        impl Player for Resigner {
+            fn chose_move(&self, _: Position) -> BitBoard {
+                unreachable!()
+            }
+
+            fn act(&self, _: &Game) -> Action {
+                Action::Resign
+            }
+        }
+        let start = Game::from_transcript("f5").unwrap().position();
+        assert_eq!(play_game(&ai(), &Resigner, start).1, RESIGN_DIFF);
+        assert_eq!(play_game(&Resigner, &ai(), Position::new()).1, -RESIGN_DIFF);
+
+        // So does a player that can't be created.
+        let create = |spec: &str| Some(Box::new(ai()) as Box<dyn Player>).filter(|_| spec == "ai");
+        assert_eq!(play_created(&create, "ai", "broken", start).1, RESIGN_DIFF);
+        assert_eq!(play_created(&create, "broken", "ai", start).1, -RESIGN_DIFF);
+        assert_eq!(play_created(&create, "broken", "broken", start).1, 0);
+        assert!(play_created(&create, "ai", "ai", start).0.position().is_game_over());
+    }
+
+    #[test]
+    fn tournament_errors() {
+        let create = |spec: &str| {
+            let ai = AlphaBetaPlayer::new(1).with_threads(1).with_hash_size(1);
+            Some(Box::new(ai) as Box<dyn Player>).filter(|_| spec == "ai")
+        };
+        let specs = |specs: &[&str]| specs.iter().map(|spec| spec.to_string()).collect::<Vec<_>>();
+
+        assert!(run_tournament(&specs(&["ai", "broken"]), 2, &[], None, create).is_err());
+        assert!(run_tournament(&specs(&["ai", "ai"]), 0, &[], None, create).is_err());
+    }
+}
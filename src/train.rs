@@ -0,0 +1,273 @@
+use crate::types::*;
+use crate::othello::*;
+use crate::position::*;
+use crate::game::*;
+use crate::ggf::*;
+use crate::wthor::*;
+use crate::transposition::*;
+use crate::endgame::*;
+use crate::files::*;
+use crate::eval::*;
+use crate::alphabeta::*;
+
+use std::io::BufRead;
+
+//#################################################################################################
+//
+//                                       CONSTANTS
+//
+//#################################################################################################
+
+/*
+ * The number of plies played at random at the start of each self-play game, so that the games
+ * differ from each other.
+ */
+const RANDOM_PLIES: usize = 10;
+
+/*
+ * The number of empty squares from which positions are labelled with their exact score, found by
+ * the endgame solver, rather than with the final score of their game.
+ */
+const SOLVE_EMPTIES: u8 = 14;
+
+/*
+ * The size of the transposition table of the endgame solver, in megabytes.
+ */
+const SOLVER_HASH_SIZE: usize = 16;
+
+/*
+ * The number of passes of gradient descent over the training positions.
+ */
+const EPOCHS: usize = 20;
+
+/*
+ * The step of the gradient descent, by which the error on a position is multiplied to update each
+ * of its weights.
+ */
+const LEARNING_RATE: f32 = 0.005;
+
+//#################################################################################################
+//
+//                                     TRAINING DATA
+//
+//#################################################################################################
+
+/*
+ * A training position, with its label: the disc difference Black is expected to finish the game
+ * with.
+ */
+struct Sample {
+    oth: Othello,
+    score: i32,
+}
+
+/*
+ * Plays a game against itself with the given AI, the first few plies being played at random, and
+ * returns every position of the game.
+ */
+fn self_play(ai: &AlphaBetaPlayer, rng: &mut Rng) -> Vec<Position> {
+    let mut pos = Position::new();
+    let mut positions: Vec<Position> = vec![];
+
+    loop {
+        if pos.must_pass() {
+            pos = pos.pass();
+        }
+        positions.push(pos);
+        if pos.is_game_over() {
+            break;
+        }
+
+        let mv = if positions.len() <= RANDOM_PLIES {
+            rng.pick(pos.legal_moves())
+        } else {
+            ai.chose_move(pos)
+        };
+        pos = pos.play(mv);
+    }
+
+    positions
+}
+
+/*
+ * Reads games from a file holding one game per line, each game being either its transcript as in
+ * "f5d6c3" or a GGF game as in GGS archives. Empty lines are skipped, and every game must be
+ * played to its end.
+ */
+fn read_games(path: &str) -> std::io::Result<Vec<Vec<Position>>> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut games: Vec<Vec<Position>> = vec![];
+
+    for (i, line) in file.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let invalid = |msg: String| invalid_data(format!("line {}: {}", i + 1, msg));
+        let game = if line.trim_start().starts_with("(;") {
+            line.parse::<GgfGame>()
+                .map(|ggf| ggf.to_game())
+                .map_err(|err| invalid(err.to_string()))?
+        } else {
+            Game::from_transcript(&line).map_err(|err| invalid(err.to_string()))?
+        };
+        if !game.position().is_game_over() {
+            return Err(invalid("unfinished game".to_owned()));
+        }
+        games.push(game.positions().to_vec());
+    }
+
+    Ok(games)
+}
+
+/*
+ * Labels every position of a finished game with its final disc difference, or with its exact
+ * score once few enough squares are empty. The final position itself is left out.
+ */
+fn label(game: &[Position], solver: &mut EndgameSolver) -> Vec<Sample> {
+    let final_diff = final_score(game[game.len() - 1].board(), Color::Black);
+
+    game[..game.len() - 1].iter().map(|&pos| {
+        let score = if pos.empties() <= SOLVE_EMPTIES {
+            let score = solver.solve(pos).0;
+            if pos.side_to_move() == Color::Black { score } else { -score }
+        } else {
+            final_diff
+        };
+        Sample { oth: pos.board(), score }
+    }).collect()
+}
+
+//#################################################################################################
+//
+//                                      FITTING
+//
+//#################################################################################################
+
+/*
+ * Fits the weights of the pattern evaluator to the samples by stochastic gradient descent on the
+ * squared error, visiting the samples in a shuffled order.
+ */
+fn fit(samples: &mut [Sample], rng: &mut Rng) -> PatternWeights {
+    let size = phase_size();
+    let mut weights: Vec<f32> = vec![0.0; NUM_PHASES * size];
+    let mut features: Vec<usize> = vec![];
+
+    for i in (1..samples.len()).rev() {
+        samples.swap(i, (rng.next_u64() % (i as u64 + 1)) as usize);
+    }
+
+    for epoch in 1..=EPOCHS {
+        let mut squared_error: f64 = 0.0;
+
+        for sample in samples.iter() {
+            let base = phase(sample.oth.empty_squares().pop_cnt()) * size;
+            features.clear();
+            for_each_feature(sample.oth, |feature| features.push(base + feature));
+
+            let predicted: f32 = features.iter().map(|&feature| weights[feature]).sum();
+            let error = (sample.score * DISC_SCALE) as f32 - predicted;
+            for &feature in &features {
+                weights[feature] += LEARNING_RATE * error;
+            }
+            squared_error += (error as f64 / DISC_SCALE as f64).powi(2);
+        }
+
+        let mean = squared_error / samples.len() as f64;
+        println!("epoch {:>2}: mean squared error {:.2}", epoch, mean);
+    }
+
+    PatternWeights::new(weights.iter()
+        .map(|w| w.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+        .collect())
+}
+
+/*
+ * Where the training games come from: self-play by an AI, a file of game records or the games of
+ * a WTHOR database kept by a filter, unfinished games being skipped.
+ */
+pub enum TrainingGames {
+    SelfPlay(usize, AlphaBetaPlayer),
+    Records(String),
+    Wthor(Wthor, WthorFilter),
+}
+
+/*
+ * Collects and labels the training positions from the given games, fits the weights of the
+ * pattern evaluator to them and saves the weights to the given path.
+ */
+pub fn run_training(games: TrainingGames, path: &str) -> std::io::Result<()> {
+    let mut rng = Rng::from_time();
+    let table = TranspositionTable::new(SOLVER_HASH_SIZE);
+    let control = Control::new(None);
+    let mut solver = EndgameSolver::new(&table, &control);
+    let mut samples: Vec<Sample> = vec![];
+
+    match games {
+        TrainingGames::SelfPlay(count, ai) => {
+            for i in 1..=count {
+                samples.extend(label(&self_play(&ai, &mut rng), &mut solver));
+                println!("game {}/{}: {} positions", i, count, samples.len());
+            }
+        },
+        TrainingGames::Records(file) => {
+            for game in read_games(&file)? {
+                samples.extend(label(&game, &mut solver));
+            }
+            println!("read {} positions from {}", samples.len(), file);
+        },
+        TrainingGames::Wthor(wthor, filter) => {
+            for game in wthor.games(&filter).filter(|game| game.game.position().is_game_over()) {
+                samples.extend(label(game.game.positions(), &mut solver));
+            }
+            println!("read {} positions from the WTHOR games", samples.len());
+        },
+    }
+
+    if samples.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "no training positions"));
+    }
+    fit(&mut samples, &mut rng).save(path)?;
+    println!("weights saved to {}", path);
+    Ok(())
+}
+
+//#################################################################################################
+//
+//                                      TRAIN TESTS
+//
+//#################################################################################################
+
+/*
+ * Derives the disc scale of the mask evaluator.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * Fits the final disc differences of 1000 depth 1 self-play games to the mask evaluations of
+     * their midgame positions, those with 20 to 49 empty squares, by least squares, and checks
+     * that the fit gives the number of evaluation units per disc of the mask evaluator.
+     */
+    #[test]
+    fn mask_disc_scale() {
+        let ai = AlphaBetaPlayer::new(1).with_endgame(0).with_wld(0).with_threads(1);
+        let mut rng = Rng::new(0x9E37_79B9_7F4A_7C15);
+        let (mut products, mut squares): (f64, f64) = (0.0, 0.0);
+
+        for _ in 0..1000 {
+            let game = self_play(&ai, &mut rng);
+            let diff = final_score(game[game.len() - 1].board(), Color::Black) as f64;
+            for pos in game.iter().filter(|pos| (20..50).contains(&pos.empties())) {
+                let eval = Evaluator::Masks.evaluate(pos.board(), Color::Black) as f64;
+                products += eval * diff;
+                squares += eval * eval;
+            }
+        }
+
+        // The fit predicts a disc difference of eval * products / squares.
+        assert_eq!((squares / products).round() as i32, Evaluator::Masks.disc_scale());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::types::*;
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+//#################################################################################################
+//
+//                                       BOUND TYPE
+//
+//#################################################################################################
+
+/*
+ * The Bound type, telling how a stored value relates to the true value of a position: it is
+ * either exactly that value, a lower bound (the search failed high) or an upper bound (the search
+ * failed low).
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact, Lower, Upper,
+}
+
+//#################################################################################################
+//
+//                                       ENTRY TYPE
+//
+//#################################################################################################
+
+/*
+ * What the table remembers about a position: the depth it was searched at, the value found with
+ * the kind of bound it is, and the best move found (0 if there was none).
+ */
+#[derive(Clone, Copy)]
+pub struct Entry {
+    pub depth: u8,
+    pub bound: Bound,
+    pub value: i32,
+    pub best_move: BitBoard,
+}
+
+impl Entry {
+    /*
+     * Packs the entry and the generation of the search that produced it in a single u64. The
+     * value takes the low 32 bits, followed by the depth, the bound, the square of the best move
+     * plus one (0 meaning no move) and the generation.
+     */
+    fn pack(&self, generation: u8) -> u64 {
+        let bound: u64 = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let square: u64 = match self.best_move {
+            0 => 0,
+            best_move => best_move.trailing_zeros() as u64 + 1,
+        };
+
+        self.value as u32 as u64
+            | (self.depth as u64) << 32
+            | bound << 40
+            | square << 42
+            | (generation as u64) << 49
+    }
+
+    /*
+     * Unpacks an entry, along with its generation, from an u64 produced by Entry::pack. As the
+     * generation is never 0, neither is the packed data of a stored entry.
+     */
+    fn unpack(data: u64) -> (Entry, u8) {
+        let bound = match (data >> 40) & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let square = (data >> 42) & 0x7F;
+
+        let entry = Entry {
+            depth: (data >> 32) as u8,
+            bound,
+            value: data as u32 as i32,
+            best_move: if square == 0 { 0 } else { 1u64 << (square - 1) },
+        };
+
+        (entry, (data >> 49) as u8 & 0x7F)
+    }
+}
+
+//#################################################################################################
+//
+//                                  TRANSPOSITION TABLE TYPE
+//
+//#################################################################################################
+
+/*
+ * A slot of the table. The key is stored xored with the data, so that a slot torn by two threads
+ * writing at the same time is detected as not matching any position and ignored.
+ */
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/*
+ * A fixed-size, lockless transposition table, indexed by the Zobrist hash of positions. It can be
+ * shared by any number of threads.
+ */
+pub struct TranspositionTable {
+    slots: Box<[Slot]>,
+    mask: usize,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /*
+     * Creates a new empty table, using at most the given number of megabytes.
+     */
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let wanted = std::cmp::max(1, megabytes * (1 << 20) / std::mem::size_of::<Slot>());
+        let len = if wanted.is_power_of_two() { wanted } else { wanted.next_power_of_two() / 2 };
+
+        let slots = (0..len)
+            .map(|_| Slot { key: AtomicU64::new(0), data: AtomicU64::new(0) })
+            .collect();
+
+        TranspositionTable { slots, mask: len - 1, generation: AtomicU8::new(0) }
+    }
+
+    /*
+     * Must be called before each new search, so that entries left by previous searches are the
+     * first ones to be replaced.
+     */
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /*
+     * Looks for the position with the given hash in the table.
+     */
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let slot = &self.slots[hash as usize & self.mask];
+        let data = slot.data.load(Ordering::Relaxed);
+
+        if slot.key.load(Ordering::Relaxed) ^ data == hash && data != 0 {
+            Some(Entry::unpack(data).0)
+        } else {
+            None
+        }
+    }
+
+    /*
+     * Stores an entry for the position with the given hash. The slot is overwritten if it holds
+     * the same position, an entry from a previous search or a shallower one.
+     */
+    pub fn store(&self, hash: u64, entry: Entry) {
+        let slot = &self.slots[hash as usize & self.mask];
+        let generation = self.generation.load(Ordering::Relaxed) % 127 + 1;
+
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let old_key = slot.key.load(Ordering::Relaxed) ^ old_data;
+        let (old, old_generation) = Entry::unpack(old_data);
+
+        let stale = old_data == 0 || old_generation != generation;
+        if stale || old_key == hash || entry.depth >= old.depth {
+            let data = entry.pack(generation);
+            slot.key.store(hash ^ data, Ordering::Relaxed);
+            slot.data.store(data, Ordering::Relaxed);
+        }
+    }
+}
+
+//#################################################################################################
+//
+//                                 TRANSPOSITION TABLE TESTS
+//
+//#################################################################################################
+
+/*
+ * Checks the packing of entries, the replacement policy of the table and the Zobrist hashes it is
+ * indexed by.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::*;
+    use crate::position::*;
+
+    fn entry(depth: u8, value: i32) -> Entry {
+        Entry { depth, bound: Bound::Lower, value, best_move: 1 << 37 }
+    }
+
+    /*
+     * Checks that two entries are the same.
+     */
+    fn assert_same(a: Entry, b: Entry) {
+        assert!(a.bound == b.bound);
+        assert_eq!((a.depth, a.value, a.best_move), (b.depth, b.value, b.best_move));
+    }
+
+    #[test]
+    fn packing() {
+        let entries = [
+            (Bound::Exact, 0, 0),
+            (Bound::Lower, -64, 1),
+            (Bound::Upper, i32::MAX, 1 << 63),
+        ];
+        for (bound, value, best_move) in entries {
+            for (depth, generation) in [(0, 1), (60, 64), (u8::MAX, 127)] {
+                let entry = Entry { depth, bound, value, best_move };
+                let (unpacked, unpacked_generation) = Entry::unpack(entry.pack(generation));
+                assert_same(unpacked, entry);
+                assert_eq!(unpacked_generation, generation);
+            }
+        }
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let table = TranspositionTable::new(1);
+        let hash: u64 = 0x0123456789ABCDEF;
+        // Another position falling in the same slot.
+        let other = hash ^ 1 << 60;
+        table.new_search();
+
+        assert!(table.probe(hash).is_none());
+        table.store(hash, entry(5, 12));
+        assert_same(table.probe(hash).unwrap(), entry(5, 12));
+        assert!(table.probe(other).is_none());
+
+        // A shallower entry doesn't replace a deeper one of the same search, unless it is for
+        // the same position.
+        table.store(other, entry(4, -3));
+        assert_same(table.probe(hash).unwrap(), entry(5, 12));
+        table.store(hash, entry(2, 7));
+        assert_same(table.probe(hash).unwrap(), entry(2, 7));
+        table.store(other, entry(2, -3));
+        assert_same(table.probe(other).unwrap(), entry(2, -3));
+        assert!(table.probe(hash).is_none());
+
+        // Entries of a previous search are always replaced.
+        table.store(hash, entry(9, 1));
+        table.new_search();
+        table.store(other, entry(1, 2));
+        assert_same(table.probe(other).unwrap(), entry(1, 2));
+    }
+
+    #[test]
+    fn zobrist_hash() {
+        // The key of a single disc, with Black to move.
+        let key = |color: Color, sq: usize| {
+            let disc: BitBoard = 1 << sq;
+            match color {
+                Color::Black => Othello::create(disc, 0).hash(Color::Black),
+                Color::White => Othello::create(0, disc).hash(Color::Black),
+            }
+        };
+
+        let other_side = |pos: Position| {
+            Position::create(pos.board(), pos.side_to_move().invert())
+        };
+
+        let mut pos = Position::new();
+        let white_to_move = pos.hash() ^ other_side(pos).hash();
+        assert_ne!(white_to_move, 0);
+
+        for _ in 0..20 {
+            let mv = pos.legal_moves() & pos.legal_moves().wrapping_neg();
+            let next = pos.play(mv);
+
+            // Updating the hash with the discs that changed gives the hash of the new position.
+            let mut incremental = pos.hash() ^ white_to_move;
+            for color in [Color::Black, Color::White] {
+                let changed = pos.board().get_bitboard(color) ^ next.board().get_bitboard(color);
+                incremental ^= (0..64)
+                    .filter(|sq| changed >> sq & 1 == 1)
+                    .fold(0, |hash, sq| hash ^ key(color, sq));
+            }
+            assert_eq!(incremental, next.hash());
+            assert_ne!(next.hash(), other_side(next).hash());
+
+            pos = if next.must_pass() { next.pass() } else { next };
+        }
+    }
+}
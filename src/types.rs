@@ -1,4 +1,7 @@
-use crate::othello::*;
+use crate::position::*;
+use crate::game::*;
+
+use std::time::Duration;
 
 //#################################################################################################
 //
@@ -15,6 +18,10 @@ pub trait BitBoardTrait {
     fn pop_lsb(&mut self) -> BitBoard;
     fn pop_cnt(self) -> u8;
     fn contains(self, x: u8, y: u8) -> bool;
+    fn flip_horizontal(self) -> BitBoard;
+    fn flip_vertical(self) -> BitBoard;
+    fn transpose(self) -> BitBoard;
+    fn transform(self, symmetry: Symmetry) -> BitBoard;
 }
 
 impl BitBoardTrait for BitBoard {
@@ -43,6 +50,105 @@ impl BitBoardTrait for BitBoard {
     fn contains(self, x: u8, y: u8) -> bool {
         1u64 << (x + 8 * y) & self != 0
     }
+
+    /*
+     * Mirrors the BitBoard left to right, exchanging the a and h columns.
+     */
+    #[inline(always)]
+    fn flip_horizontal(self) -> BitBoard {
+        let mut b = self;
+        b = ((b >> 1) & 0x5555555555555555) | ((b & 0x5555555555555555) << 1);
+        b = ((b >> 2) & 0x3333333333333333) | ((b & 0x3333333333333333) << 2);
+        ((b >> 4) & 0x0F0F0F0F0F0F0F0F) | ((b & 0x0F0F0F0F0F0F0F0F) << 4)
+    }
+
+    /*
+     * Mirrors the BitBoard top to bottom, exchanging the 1 and 8 rows.
+     */
+    #[inline(always)]
+    fn flip_vertical(self) -> BitBoard {
+        self.swap_bytes()
+    }
+
+    /*
+     * Mirrors the BitBoard along the a1-h8 diagonal, exchanging rows and columns.
+     */
+    #[inline(always)]
+    fn transpose(self) -> BitBoard {
+        let mut b = self;
+        let mut t: BitBoard;
+        t = 0x0F0F0F0F00000000 & (b ^ (b << 28));
+        b ^= t ^ (t >> 28);
+        t = 0x3333000033330000 & (b ^ (b << 14));
+        b ^= t ^ (t >> 14);
+        t = 0x5500550055005500 & (b ^ (b << 7));
+        b ^ t ^ (t >> 7)
+    }
+
+    /*
+     * Applies the given symmetry to the BitBoard.
+     */
+    #[inline(always)]
+    fn transform(self, symmetry: Symmetry) -> BitBoard {
+        let mut b = self;
+        if symmetry.0 & Symmetry::FLIP_HORIZONTAL != 0 { b = b.flip_horizontal(); }
+        if symmetry.0 & Symmetry::FLIP_VERTICAL != 0 { b = b.flip_vertical(); }
+        if symmetry.0 & Symmetry::TRANSPOSE != 0 { b = b.transpose(); }
+        b
+    }
+}
+
+//#################################################################################################
+//
+//                                     SYMMETRY TYPE
+//
+//#################################################################################################
+
+/*
+ * One of the eight symmetries of the board (the dihedral group D4): a left to right mirror, then
+ * a top to bottom mirror, then a transposition along the a1-h8 diagonal, each of them being
+ * applied or not. Rotations are the symmetries combining a transposition with a single mirror.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Symmetry(u8);
+
+impl Symmetry {
+    const FLIP_HORIZONTAL: u8 = 1;
+    const FLIP_VERTICAL: u8 = 2;
+    const TRANSPOSE: u8 = 4;
+
+    /*
+     * Returns the eight symmetries, the identity first.
+     */
+    pub fn all() -> impl Iterator<Item = Symmetry> {
+        (0..8).map(Symmetry)
+    }
+
+    /*
+     * Returns the square (x + 8 * y, a1 being 0) the given square is sent to by the symmetry.
+     */
+    pub fn transform_square(self, sq: u8) -> u8 {
+        let (mut x, mut y) = (sq % 8, sq / 8);
+        if self.0 & Symmetry::FLIP_HORIZONTAL != 0 { x = 7 - x; }
+        if self.0 & Symmetry::FLIP_VERTICAL != 0 { y = 7 - y; }
+        if self.0 & Symmetry::TRANSPOSE != 0 { std::mem::swap(&mut x, &mut y); }
+        x + 8 * y
+    }
+
+    /*
+     * Returns the symmetry undoing this one. Mirrors undo themselves, but a transposition turns a
+     * left to right mirror applied before it into a top to bottom mirror applied after it, so a
+     * rotation is undone by the opposite rotation.
+     */
+    pub fn inverse(self) -> Symmetry {
+        let both = Symmetry::FLIP_HORIZONTAL | Symmetry::FLIP_VERTICAL;
+        let flips = self.0 & both;
+        if self.0 & Symmetry::TRANSPOSE != 0 && flips != 0 && flips != both {
+            Symmetry(Symmetry::TRANSPOSE | (flips ^ both))
+        } else {
+            self
+        }
+    }
 }
 
 //#################################################################################################
@@ -54,7 +160,7 @@ impl BitBoardTrait for BitBoard {
 /*
  * The Color type, an enum that can either be White or Black.
  */
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum Color {
     Black, White,
 }
@@ -87,6 +193,103 @@ pub enum Square {
     Empty, Black, White,
 }
 
+//#################################################################################################
+//
+//                                        MOVE TYPE
+//
+//#################################################################################################
+
+/*
+ * A move in algebraic notation: either a square, from a1 (0) to h8 (63), or a pass. Converts to
+ * and from the single-bit BitBoards, or 0 for a pass, used everywhere else, a square past h8
+ * being rejected then.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Move {
+    Square(u8),
+    Pass,
+}
+
+impl From<BitBoard> for Move {
+    /*
+     * Returns the move of a BitBoard with a single bit set, or the pass if it is 0.
+     */
+    fn from(mv: BitBoard) -> Move {
+        debug_assert!(mv & mv.wrapping_sub(1) == 0, "Not a single move");
+        if mv == 0 { Move::Pass } else { Move::Square(mv.trailing_zeros() as u8) }
+    }
+}
+
+impl From<Move> for BitBoard {
+    /*
+     * Returns the BitBoard with the bit of the square of the move set, or 0 for a pass. Panics if
+     * the square is past h8.
+     */
+    fn from(mv: Move) -> BitBoard {
+        match mv {
+            Move::Square(sq) => {
+                assert!(sq < 64, "Not a square");
+                1u64 << sq
+            },
+            Move::Pass => 0,
+        }
+    }
+}
+
+impl std::fmt::Display for Move {
+    /*
+     * Formats the move as its square in lowercase, as in "f5", or "--" for a pass.
+     */
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Move::Square(sq) => write!(f, "{}{}", (b'a' + sq % 8) as char, sq / 8 + 1),
+            Move::Pass => write!(f, "--"),
+        }
+    }
+}
+
+/*
+ * The error returned when parsing an invalid move, holding the input.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseMoveError(String);
+
+impl std::fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid move \"{}\", expected a square from a1 to h8 or a pass (\"PA\" or \"--\")",
+            self.0,
+        )
+    }
+}
+
+impl std::error::Error for ParseMoveError {}
+
+impl std::str::FromStr for Move {
+    type Err = ParseMoveError;
+
+    /*
+     * Parses a move from a square, as in "f5" or "F5", or a pass, written "PA", "pass" or "--"
+     * in any case. Surrounding whitespace, such as a line ending, is ignored.
+     */
+    fn from_str(s: &str) -> Result<Move, ParseMoveError> {
+        let input = s.trim();
+
+        let pass = input.eq_ignore_ascii_case("pa") || input.eq_ignore_ascii_case("pass");
+        if input == "--" || pass {
+            return Ok(Move::Pass);
+        }
+
+        match input.as_bytes() {
+            [x, y @ b'1'..=b'8'] if (b'a'..=b'h').contains(&x.to_ascii_lowercase()) => {
+                Ok(Move::Square((x.to_ascii_lowercase() - b'a') + 8 * (y - b'1')))
+            },
+            _ => Err(ParseMoveError(input.to_owned())),
+        }
+    }
+}
+
 //#################################################################################################
 //
 //                                    PLAYER TRAIT
@@ -97,7 +300,114 @@ pub enum Square {
  * A trait representing a player by it's means of choosing a move.
  */
 pub trait Player {
-    fn chose_move(&self, oth: Othello, moves: BitBoard, color: Color) -> BitBoard;
+    fn chose_move(&self, pos: Position) -> BitBoard;
+
+    /*
+     * Chooses a move like chose_move, and also reports what the player found on the way. Players
+     * that don't search only report the move.
+     */
+    fn think(&self, pos: Position) -> SearchResult {
+        SearchResult::new(self.chose_move(pos))
+    }
+
+    /*
+     * Decides what to do on the player's turn in the game. Players play the move found by think,
+     * unless they can also move through the history of the game.
+     */
+    fn act(&self, game: &Game) -> Action {
+        Action::Play(self.think(game.position()))
+    }
+}
+
+/*
+ * What a player does on its turn: play a move, with what it found while thinking about it, take
+ * back its last move, replay the move it took back, go to the position after the given number
+ * of plies, or resign, losing the game.
+ */
+pub enum Action {
+    Play(SearchResult),
+    Undo,
+    Redo,
+    Jump(usize),
+    Resign,
+}
+
+//#################################################################################################
+//
+//                                  SEARCH RESULT TYPE
+//
+//#################################################################################################
+
+/*
+ * The ScoreKind type, telling what the score of a search means: either a heuristic value from the
+ * evaluation function, the exact final disc difference, or only the sign of it (won, drawn or
+ * lost).
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum ScoreKind {
+    Heuristic, Exact, WinLossDraw,
+}
+
+/*
+ * The outcome of a search: the best move found, its score from the point of view of the player
+ * to move, the depth reached in plies, the number of nodes visited, the time spent and the
+ * principal variation, the line both players are expected to follow, where 0 stands for a pass.
+ */
+#[derive(Clone)]
+pub struct SearchResult {
+    pub best_move: BitBoard,
+    pub score: i32,
+    pub kind: ScoreKind,
+    pub depth: u8,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub pv: Vec<BitBoard>,
+}
+
+impl SearchResult {
+    /*
+     * Creates the result of a search that only found the given move, without searching.
+     */
+    pub fn new(best_move: BitBoard) -> SearchResult {
+        SearchResult {
+            best_move,
+            score: 0,
+            kind: ScoreKind::Heuristic,
+            depth: 0,
+            nodes: 0,
+            elapsed: Duration::from_secs(0),
+            pv: vec![best_move],
+        }
+    }
+
+    /*
+     * Returns the number of nodes visited per second.
+     */
+    pub fn nps(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { self.nodes as f64 / secs } else { 0.0 }
+    }
+}
+
+impl std::fmt::Display for SearchResult {
+    /*
+     * Formats the result on a single line, for the terminal and the logs.
+     */
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let score = match self.kind {
+            ScoreKind::Heuristic => format!("{:+}", self.score),
+            ScoreKind::Exact => format!("{:+} (exact)", self.score),
+            ScoreKind::WinLossDraw => match self.score.signum() {
+                1 => "win".to_owned(),
+                0 => "draw".to_owned(),
+                _ => "loss".to_owned(),
+            },
+        };
+        let pv: Vec<String> = self.pv.iter().map(|&mv| Move::from(mv).to_string()).collect();
+
+        write!(f, "depth {} score {} nodes {} time {:.3}s nps {:.0} pv {}",
+            self.depth, score, self.nodes, self.elapsed.as_secs_f64(), self.nps(), pv.join(" "))
+    }
 }
 
 //#################################################################################################
@@ -129,3 +439,93 @@ impl Score {
         }
     }
 }
+
+//#################################################################################################
+//
+//                                        RNG TYPE
+//
+//#################################################################################################
+
+/*
+ * A small xorshift pseudo-random number generator, enough to vary the games played by the AI.
+ */
+pub struct Rng(u64);
+
+impl Rng {
+    /*
+     * Creates a new Rng from the given seed, which must not be 0.
+     */
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    /*
+     * Creates a new Rng seeded from the clock.
+     */
+    pub fn from_time() -> Rng {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Rng(nanos | 1)
+    }
+
+    /*
+     * Returns the next pseudo-random number.
+     */
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /*
+     * Returns one of the moves of the given non-empty set, picked at random.
+     */
+    pub fn pick(&mut self, mut moves: BitBoard) -> BitBoard {
+        for _ in 0..self.next_u64() % moves.pop_cnt() as u64 {
+            moves.pop_lsb();
+        }
+        moves & moves.wrapping_neg()
+    }
+}
+
+//#################################################################################################
+//
+//                                       TYPES TEST
+//
+//#################################################################################################
+
+/*
+ * Checks the parsing and formatting of moves.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_notation() {
+        assert_eq!("f5".parse::<Move>(), Ok(Move::Square(37)));
+        assert_eq!("F5\r\n".parse::<Move>(), Ok(Move::Square(37)));
+        assert_eq!("h8".parse::<Move>(), Ok(Move::Square(63)));
+        assert_eq!("PA".parse::<Move>(), Ok(Move::Pass));
+        assert_eq!("--".parse::<Move>(), Ok(Move::Pass));
+        for invalid in ["i1", "a9", ""] {
+            assert!(invalid.parse::<Move>().is_err());
+        }
+
+        for sq in 0..64 {
+            let mv = Move::Square(sq);
+            assert_eq!(mv.to_string().parse::<Move>(), Ok(mv));
+            assert_eq!(Move::from(BitBoard::from(mv)), mv);
+        }
+        assert_eq!(Move::Pass.to_string(), "--");
+        assert_eq!(Move::from(0), Move::Pass);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not a square")]
+    fn move_past_h8() {
+        let _ = BitBoard::from(Move::Square(64));
+    }
+}
@@ -0,0 +1,345 @@
+use crate::position::*;
+use crate::game::*;
+use crate::ggf::*;
+use crate::files::invalid_data;
+
+use std::io::Read;
+
+//#################################################################################################
+//
+//                                       CONSTANTS
+//
+//#################################################################################################
+
+/*
+ * The size of the header of every WTHOR file.
+ */
+const HEADER_SIZE: usize = 16;
+
+/*
+ * The size of a game record of a .wtb file, and of a name record of the .jou (players) and .trn
+ * (tournaments) files.
+ */
+const GAME_SIZE: usize = 68;
+const PLAYER_SIZE: usize = 20;
+const TOURNAMENT_SIZE: usize = 26;
+
+//#################################################################################################
+//
+//                                     WTHOR GAME TYPE
+//
+//#################################################################################################
+
+/*
+ * A game of a WTHOR database: the numbers of its tournament and players, which are indices in the
+ * .trn and .jou files, its year, the number of black discs at its end, and the game itself,
+ * replayed from its moves.
+ */
+pub struct WthorGame {
+    pub tournament: u16,
+    pub black: u16,
+    pub white: u16,
+    pub year: u16,
+    pub black_discs: u8,
+    pub game: Game,
+}
+
+/*
+ * A game of a WTHOR file skipped for an illegal move: its number in the file, the byte of the
+ * move and its ply, both numbers counting from 1.
+ */
+#[derive(Debug, PartialEq)]
+pub struct SkippedGame {
+    pub number: usize,
+    pub byte: u8,
+    pub ply: usize,
+}
+
+/*
+ * Reads the header of a WTHOR file, returning the number of records it announces: the u32 at
+ * offset 4 for game files, the u16 at offset 8 for name files. Game files also give their year at
+ * offset 10, and their board size at offset 12, either 0 or 8 for 8x8 boards.
+ */
+fn read_header(reader: &mut impl Read, games: bool) -> std::io::Result<(usize, u16)> {
+    let mut header = [0u8; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+
+    if games {
+        if header[12] != 0 && header[12] != 8 {
+            return Err(invalid_data("unsupported board size in WTHOR file"));
+        }
+        let count = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        Ok((count, u16::from_le_bytes([header[10], header[11]])))
+    } else {
+        Ok((u16::from_le_bytes([header[8], header[9]]) as usize, 0))
+    }
+}
+
+/*
+ * Reads the games of a .wtb file. A game record holds its tournament, black player and white
+ * player numbers as little-endian u16, the number of black discs at the end and the theoretical
+ * score, which is left out, as bytes, then its 60 moves as bytes worth 10 * row + column (from 1
+ * to 8, "11" being a1), 0 once the game is over. Passes are implicit. Games with an illegal move,
+ * which real databases hold a few of, are skipped and returned after the games read.
+ */
+pub fn read_wthor_games(
+    mut reader: impl Read,
+) -> std::io::Result<(Vec<WthorGame>, Vec<SkippedGame>)> {
+    let (count, year) = read_header(&mut reader, true)?;
+    let mut games: Vec<WthorGame> = Vec::with_capacity(count);
+    let mut skipped: Vec<SkippedGame> = vec![];
+
+    'records: for i in 0..count {
+        let mut record = [0u8; GAME_SIZE];
+        reader.read_exact(&mut record)?;
+
+        let mut game: Game = Game::new();
+        for &byte in record[8..].iter().take_while(|&&byte| byte != 0) {
+            let (row, column) = (byte / 10, byte % 10);
+            let legal = (1..=8).contains(&row)
+                && (1..=8).contains(&column)
+                && game.play(1 << (8 * (row - 1) + column - 1));
+            if !legal {
+                skipped.push(SkippedGame { number: i + 1, byte, ply: game.ply() + 1 });
+                continue 'records;
+            }
+            if game.position().must_pass() {
+                game.play(0);
+            }
+        }
+
+        games.push(WthorGame {
+            tournament: u16::from_le_bytes([record[0], record[1]]),
+            black: u16::from_le_bytes([record[2], record[3]]),
+            white: u16::from_le_bytes([record[4], record[5]]),
+            year,
+            black_discs: record[6],
+            game,
+        });
+    }
+
+    Ok((games, skipped))
+}
+
+/*
+ * Reads the names of a .jou or .trn file, whose records have the given size, each name being
+ * encoded in ISO-8859-1 and padded with zeros.
+ */
+fn read_names(mut reader: impl Read, size: usize) -> std::io::Result<Vec<String>> {
+    let (count, _) = read_header(&mut reader, false)?;
+    let mut record: Vec<u8> = vec![0; size];
+    let mut names: Vec<String> = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        reader.read_exact(&mut record)?;
+        let name: String =
+            record.iter().take_while(|&&byte| byte != 0).map(|&byte| byte as char).collect();
+        names.push(name.trim_end().to_owned());
+    }
+
+    Ok(names)
+}
+
+//#################################################################################################
+//
+//                                     WTHOR DATABASE
+//
+//#################################################################################################
+
+/*
+ * Which games of a WTHOR database to keep: those of a player, as black or white, and of a
+ * tournament whose names contain the given ones regardless of case, and of the given year.
+ */
+#[derive(Default)]
+pub struct WthorFilter {
+    pub player: Option<String>,
+    pub tournament: Option<String>,
+    pub year: Option<u16>,
+}
+
+/*
+ * A WTHOR database, as published by the Fédération Française d'Othello: the games of .wtb files,
+ * usually one per year, along with the names of the players and tournaments.
+ */
+pub struct Wthor {
+    players: Vec<String>,
+    tournaments: Vec<String>,
+    games: Vec<WthorGame>,
+}
+
+impl Wthor {
+    /*
+     * Creates a new empty Wthor database.
+     */
+    pub fn new() -> Wthor {
+        Wthor { players: vec![], tournaments: vec![], games: vec![] }
+    }
+
+    /*
+     * Loads the names of the players from the .jou file at the given path.
+     */
+    pub fn load_players(&mut self, path: &str) -> std::io::Result<()> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        self.players = read_names(file, PLAYER_SIZE)?;
+        Ok(())
+    }
+
+    /*
+     * Loads the names of the tournaments from the .trn file at the given path.
+     */
+    pub fn load_tournaments(&mut self, path: &str) -> std::io::Result<()> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        self.tournaments = read_names(file, TOURNAMENT_SIZE)?;
+        Ok(())
+    }
+
+    /*
+     * Adds the games of the .wtb file at the given path. Returns the games skipped for an illegal
+     * move.
+     */
+    pub fn load_games(&mut self, path: &str) -> std::io::Result<Vec<SkippedGame>> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let (games, skipped) = read_wthor_games(file)?;
+        self.games.extend(games);
+        Ok(skipped)
+    }
+
+    /*
+     * Returns the name of the player with the given number, or "?" if it is unknown.
+     */
+    pub fn player(&self, number: u16) -> &str {
+        self.players.get(number as usize).map_or("?", |name| name.as_str())
+    }
+
+    /*
+     * Returns the name of the tournament with the given number, or "?" if it is unknown.
+     */
+    pub fn tournament(&self, number: u16) -> &str {
+        self.tournaments.get(number as usize).map_or("?", |name| name.as_str())
+    }
+
+    /*
+     * Returns the games kept by the filter.
+     */
+    pub fn games<'a>(&'a self, filter: &'a WthorFilter) -> impl Iterator<Item = &'a WthorGame> {
+        let contains = |name: &str, part: &Option<String>| {
+            part.as_ref().is_none_or(|part| name.to_lowercase().contains(&part.to_lowercase()))
+        };
+
+        self.games.iter().filter(move |game| {
+            let player = |number: u16| contains(self.player(number), &filter.player);
+            (player(game.black) || player(game.white))
+                && contains(self.tournament(game.tournament), &filter.tournament)
+                && filter.year.is_none_or(|year| game.year == year)
+        })
+    }
+
+    /*
+     * Returns every position of the games kept by the filter.
+     */
+    pub fn positions<'a>(
+        &'a self,
+        filter: &'a WthorFilter,
+    ) -> impl Iterator<Item = Position> + 'a {
+        self.games(filter).flat_map(|game| game.game.positions().iter().copied())
+    }
+
+    /*
+     * Returns a game of the database as a GGF game, with its year as date and its result from the
+     * number of black discs at its end.
+     */
+    pub fn to_ggf(&self, game: &WthorGame) -> GgfGame {
+        let mut ggf = GgfGame::from_game(&game.game);
+        ggf.place = self.tournament(game.tournament).to_owned();
+        ggf.date = game.year.to_string();
+        ggf.black = self.player(game.black).to_owned();
+        ggf.white = self.player(game.white).to_owned();
+        ggf.result = Some(2.0 * game.black_discs as f32 - 64.0);
+        ggf
+    }
+}
+
+//#################################################################################################
+//
+//                                      WTHOR TESTS
+//
+//#################################################################################################
+
+/*
+ * Checks the reading and filtering of a small database.
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(games: u32, names: u16, year: u16) -> Vec<u8> {
+        let mut header: Vec<u8> = vec![20, 24, 1, 1];
+        header.extend(games.to_le_bytes());
+        header.extend(names.to_le_bytes());
+        header.extend(year.to_le_bytes());
+        header.extend([8, 0, 0, 0]);
+        header
+    }
+
+    fn names(names: &[&str], size: usize) -> Vec<u8> {
+        let mut file = header(0, names.len() as u16, 0);
+        for name in names {
+            let mut record = name.as_bytes().to_vec();
+            record.resize(size, 0);
+            file.extend(record);
+        }
+        file
+    }
+
+    #[test]
+    fn read_database() {
+        let mut file = header(2, 0, 1999);
+        let records = [(0u16, 0u16, 1u16, &[56u8, 64, 33][..]), (1, 2, 0, &[56, 46][..])];
+        for (tournament, black, white, moves) in records {
+            file.extend(tournament.to_le_bytes());
+            file.extend(black.to_le_bytes());
+            file.extend(white.to_le_bytes());
+            file.extend([33, 32]);
+            let mut record = moves.to_vec();
+            record.resize(60, 0);
+            file.extend(record);
+        }
+
+        let mut wthor = Wthor::new();
+        let (games, skipped) = read_wthor_games(file.as_slice()).unwrap();
+        assert!(skipped.is_empty());
+        wthor.games = games;
+        let read = |list: &[&str], size| read_names(names(list, size).as_slice(), size).unwrap();
+        wthor.players = read(&["Tamenori Hideshi", "Shaman Brian", "Tastet Marc"], PLAYER_SIZE);
+        wthor.tournaments = read(&["Paris", "Championnat du Monde"], TOURNAMENT_SIZE);
+
+        let first = &wthor.games[0];
+        assert_eq!(first.game.position(), Game::from_transcript("f5d6c3").unwrap().position());
+        assert_eq!(wthor.player(first.white), "Shaman Brian");
+        assert_eq!(wthor.to_ggf(first).result, Some(2.0));
+
+        let count = |filter: &WthorFilter| wthor.games(filter).count();
+        assert_eq!(count(&WthorFilter::default()), 2);
+        let player = |name: &str| {
+            WthorFilter { player: Some(name.to_owned()), ..Default::default() }
+        };
+        assert_eq!(count(&player("tamenori")), 2);
+        assert_eq!(count(&player("tastet")), 1);
+        let tournament = |name: &str| {
+            WthorFilter { tournament: Some(name.to_owned()), ..Default::default() }
+        };
+        assert_eq!(count(&tournament("monde")), 1);
+        assert_eq!(count(&WthorFilter { year: Some(2000), ..Default::default() }), 0);
+        assert_eq!(wthor.positions(&WthorFilter::default()).count(), 4 + 3);
+
+        // f5 then f4 is illegal for White, so the second game is skipped.
+        file[HEADER_SIZE + GAME_SIZE + 9] = 46 - 10;
+        let (games, skipped) = read_wthor_games(file.as_slice()).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].game.transcript(), "f5d6c3");
+        assert_eq!(skipped, [SkippedGame { number: 2, byte: 36, ply: 2 }]);
+
+        // A truncated file is still an error.
+        assert!(read_wthor_games(&file[..file.len() - 1]).is_err());
+    }
+}